@@ -0,0 +1,46 @@
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use table_driven_lexer::{Lexer, lex_operators, table_lex};
+
+/// Repeats `unit` until the input is at least `size` bytes.
+fn input(unit: &str, size: usize) -> String {
+    unit.repeat(size.div_ceil(unit.len()))
+}
+
+fn inputs() -> [(&'static str, String); 3] {
+    [
+        ("punctuation", input("a::b = c; d: e, f := g\n", 100 * 1024)),
+        (
+            "identifiers",
+            input("let some_long_name: string = other_name;\n", 100 * 1024),
+        ),
+        (
+            "strings",
+            input(
+                "let s = \"a fairly long string literal value\";\n",
+                100 * 1024,
+            ),
+        ),
+    ]
+}
+
+fn bench_lexers(c: &mut Criterion) {
+    for (name, source) in inputs() {
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function("table_lex", |b| b.iter(|| table_lex(black_box(&source))));
+        // `lex` is an alias for `table_lex`, so it gets no group of its own.
+        let lexer = Lexer::new();
+        group.bench_function("lex_raw", |b| b.iter(|| lexer.lex_raw(black_box(&source))));
+        // The operator trie only finds punctuation, so it is timed on the
+        // punctuation-heavy input alone.
+        if name == "punctuation" {
+            group.bench_function("lex_operator", |b| {
+                b.iter(|| lex_operators(black_box(&source)))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_lexers);
+criterion_main!(benches);
@@ -1,5 +1,11 @@
 pub trait GfmChar {
     fn is_ascii_punctuation_character(&self) -> bool;
+
+    /// GFM "Unicode whitespace": tab, line feed, form feed, carriage
+    /// return, or any character in the Unicode `Zs` category. That
+    /// includes the non-breaking space (U+00A0), but not vertical tab or
+    /// the line/paragraph separators that [`char::is_whitespace`] accepts.
+    fn is_unicode_whitespace(&self) -> bool;
 }
 
 impl GfmChar for char {
@@ -39,5 +45,37 @@ impl GfmChar for char {
                 | '~'
         )
     }
+
+    fn is_unicode_whitespace(&self) -> bool {
+        std::matches!(
+            self,
+            '\t' | '\n'
+                | '\u{c}'
+                | '\r'
+                | ' '
+                | '\u{a0}'
+                | '\u{1680}'
+                | '\u{2000}'..='\u{200a}'
+                | '\u{202f}'
+                | '\u{205f}'
+                | '\u{3000}'
+        )
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gfm_whitespace() {
+        for c in ['\t', '\n', '\u{c}', '\r', ' ', '\u{3000}'] {
+            assert!(c.is_unicode_whitespace(), "{c:?}");
+        }
+        // Non-breaking space is in `Zs`, so GFM treats it as whitespace.
+        assert!('\u{a0}'.is_unicode_whitespace());
+        assert!(!'\u{b}'.is_unicode_whitespace());
+        assert!(!'\u{2028}'.is_unicode_whitespace());
+        assert!(!'a'.is_unicode_whitespace());
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use crate::{Cursor, Span, SyntaxKind, Token, TokenData};
+
+use super::GfmChar;
+
+/// Splits Markdown source into `Whitespace` runs, single ASCII
+/// `Punctuation` characters, and `Text` runs of everything else, as the
+/// GFM inline rules see them. Newlines are `NewLine` tokens.
+pub fn lex_markdown(source: &str) -> Vec<Token> {
+    let mut cursor = Cursor::new(source);
+    let mut tokens = Vec::new();
+
+    while let Some(c) = cursor.peek() {
+        let start = cursor.offset();
+        let kind = if c == '\n' {
+            SyntaxKind::NewLine
+        } else if c.is_unicode_whitespace() {
+            SyntaxKind::Whitespace
+        } else if c.is_ascii_punctuation_character() {
+            SyntaxKind::Punctuation
+        } else {
+            SyntaxKind::Text
+        };
+
+        cursor.next();
+        // Punctuation and newlines stay one character each, so delimiter
+        // runs can be measured by the caller.
+        if matches!(kind, SyntaxKind::Whitespace | SyntaxKind::Text) {
+            while let Some(next) = cursor.peek() {
+                let same = match kind {
+                    SyntaxKind::Whitespace => next.is_unicode_whitespace() && next != '\n',
+                    _ => !next.is_unicode_whitespace() && !next.is_ascii_punctuation_character(),
+                };
+                if !same {
+                    break;
+                }
+                cursor.next();
+            }
+        }
+
+        let span: Span = cursor.span_from(start);
+        tokens.push(Arc::new(TokenData {
+            kind,
+            text: source[span.start..span.end].to_string().into(),
+            span,
+            symbol: None,
+            raw: None,
+        }));
+    }
+    tokens
+}
+
+/// Which sides of a `*` or `_` delimiter run can open or close emphasis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelimiterFlanking {
+    Left,
+    Right,
+    Both,
+    Neither,
+}
+
+/// Classifies `run` using the CommonMark flanking rules, given the
+/// characters around it. `None` stands for the start or end of the line
+/// and counts as whitespace. Only ASCII punctuation is recognised as
+/// punctuation. Anything other than a run of one delimiter character is
+/// `Neither`.
+pub fn classify_delimiter_run(
+    before: Option<char>,
+    run: &str,
+    after: Option<char>,
+) -> DelimiterFlanking {
+    let mut chars = run.chars();
+    let Some(delim @ ('*' | '_')) = chars.next() else {
+        return DelimiterFlanking::Neither;
+    };
+    if !chars.all(|c| c == delim) {
+        return DelimiterFlanking::Neither;
+    }
+
+    let whitespace = |c: Option<char>| c.is_none_or(|c| c.is_unicode_whitespace());
+    let punctuation = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation_character());
+
+    let left =
+        !whitespace(after) && (!punctuation(after) || whitespace(before) || punctuation(before));
+    let right =
+        !whitespace(before) && (!punctuation(before) || whitespace(after) || punctuation(after));
+
+    match (left, right) {
+        (true, true) => DelimiterFlanking::Both,
+        (true, false) => DelimiterFlanking::Left,
+        (false, true) => DelimiterFlanking::Right,
+        (false, false) => DelimiterFlanking::Neither,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexes_bold_text() {
+        let tokens = lex_markdown("**bold**, text");
+        let kinds: Vec<_> = tokens.iter().map(|t| (t.kind, t.text.as_ref())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (SyntaxKind::Punctuation, "*"),
+                (SyntaxKind::Punctuation, "*"),
+                (SyntaxKind::Text, "bold"),
+                (SyntaxKind::Punctuation, "*"),
+                (SyntaxKind::Punctuation, "*"),
+                (SyntaxKind::Punctuation, ","),
+                (SyntaxKind::Whitespace, " "),
+                (SyntaxKind::Text, "text"),
+            ]
+        );
+        assert_eq!(tokens[2].span, Span::new(2, 6));
+    }
+
+    /// Splits `line` around its first delimiter run and classifies it.
+    fn flanking(line: &str) -> DelimiterFlanking {
+        let start = line.find(['*', '_']).unwrap();
+        let delim = line[start..].chars().next().unwrap();
+        let len = line[start..]
+            .find(|c| c != delim)
+            .unwrap_or(line.len() - start);
+        let end = start + len;
+        classify_delimiter_run(
+            line[..start].chars().next_back(),
+            &line[start..end],
+            line[end..].chars().next(),
+        )
+    }
+
+    #[test]
+    fn commonmark_flanking_examples() {
+        use DelimiterFlanking::*;
+        for line in ["***abc", "  _abc", "**\"abc\"", " _\"abc\""] {
+            assert_eq!(flanking(line), Left, "{line:?}");
+        }
+        for line in [" abc***", " abc_", "\"abc\"**", "\"abc\"_"] {
+            assert_eq!(flanking(line), Right, "{line:?}");
+        }
+        for line in [" abc***def", "\"abc\"_\"def\""] {
+            assert_eq!(flanking(line), Both, "{line:?}");
+        }
+        for line in ["abc *** def", "a _ b"] {
+            assert_eq!(flanking(line), Neither, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn mixed_runs_are_not_delimiters() {
+        assert_eq!(
+            classify_delimiter_run(None, "*_", Some('a')),
+            DelimiterFlanking::Neither
+        );
+        assert_eq!(
+            classify_delimiter_run(None, "", Some('a')),
+            DelimiterFlanking::Neither
+        );
+    }
+
+    #[test]
+    fn non_ascii_punctuation_is_text() {
+        let tokens = lex_markdown("a«b»\n");
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [SyntaxKind::Text, SyntaxKind::NewLine]);
+    }
+}
@@ -1,3 +1,5 @@
 mod gfm;
+mod markdown;
 
 pub use gfm::*;
+pub use markdown::*;
@@ -0,0 +1,165 @@
+use crate::{Span, SyntaxElement, SyntaxKind, SyntaxNode, Token, string_contents, unescape};
+
+/// Typed view over a `VarDecl` node.
+///
+/// Unlike the owned [`VarDecl`](crate::VarDecl) produced by `lower_to_ast`,
+/// this keeps the underlying CST node, so every accessor hands back the
+/// original token rather than a copy of its text.
+#[derive(Debug, Clone)]
+pub struct VarDeclNode(SyntaxNode);
+
+impl VarDeclNode {
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        if node.kind() == SyntaxKind::VarDecl {
+            Some(VarDeclNode(node))
+        } else {
+            None
+        }
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+
+    pub fn name_token(&self) -> Option<Token> {
+        self.token_of(SyntaxKind::Ident)
+    }
+
+    pub fn type_token(&self) -> Option<Token> {
+        self.token_of(SyntaxKind::Type)
+    }
+
+    /// The token inside the declaration's `Value` node.
+    pub fn value_token(&self) -> Option<Token> {
+        self.0
+            .child_nodes()
+            .into_iter()
+            .find(|n| n.kind() == SyntaxKind::Value)
+            .and_then(|n| n.tokens().first().map(|t| Token::clone(t)))
+    }
+
+    fn token_of(&self, kind: SyntaxKind) -> Option<Token> {
+        self.0
+            .tokens()
+            .into_iter()
+            .find(|t| t.kind == kind)
+            .cloned()
+    }
+}
+
+/// Every string literal in the tree with its unescaped value and the span
+/// of the literal (quotes included), in source order.
+pub fn collect_string_literals(root: &SyntaxNode) -> Vec<(String, Span)> {
+    let mut literals = Vec::new();
+    collect_kind(root, SyntaxKind::StringLiteral, &mut literals);
+    literals
+        .into_iter()
+        .map(|tok| {
+            // Text that differs from the literal as written was already
+            // decoded by the lexer.
+            let value = if tok.text != string_contents(&tok) {
+                tok.text.to_string()
+            } else {
+                unescape(&tok.text).unwrap_or_else(|_| tok.text.to_string())
+            };
+            (value, tok.span)
+        })
+        .collect()
+}
+
+fn collect_kind(node: &SyntaxNode, kind: SyntaxKind, out: &mut Vec<Token>) {
+    for child in &node.children {
+        match child {
+            SyntaxElement::Token(tok) if tok.kind == kind => out.push(tok.clone()),
+            SyntaxElement::Token(_) => {}
+            SyntaxElement::Node(n) => collect_kind(n, kind, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_tokens_to_cst, table_lex, Lexer, SyntaxNodeData, TokenData};
+
+    fn token(kind: SyntaxKind, text: &str) -> SyntaxElement {
+        SyntaxElement::Token(Token::new(TokenData {
+            kind,
+            text: text.to_string().into(),
+            span: Span::default(),
+            symbol: None,
+            raw: None,
+        }))
+    }
+
+    #[test]
+    fn accessors_read_the_underlying_tokens() {
+        let node: SyntaxNode = SyntaxNodeData::new(
+            SyntaxKind::VarDecl,
+            vec![
+                token(SyntaxKind::Let, "let"),
+                token(SyntaxKind::Ident, "name"),
+                token(SyntaxKind::Colon, ":"),
+                token(SyntaxKind::Type, "string"),
+                token(SyntaxKind::Equal, "="),
+                SyntaxElement::Node(SyntaxNodeData::build(
+                    SyntaxKind::Value,
+                    vec![token(SyntaxKind::StringLiteral, "Abhi")],
+                )),
+                token(SyntaxKind::Semicolon, ";"),
+            ],
+        )
+        .into();
+
+        let decl = VarDeclNode::cast(node).unwrap();
+        assert_eq!(decl.name_token().unwrap().text, "name");
+        assert_eq!(decl.type_token().unwrap().text, "string");
+        assert_eq!(decl.value_token().unwrap().text, "Abhi");
+    }
+
+    #[test]
+    fn missing_parts_are_none() {
+        let node: SyntaxNode =
+            SyntaxNodeData::new(SyntaxKind::VarDecl, vec![token(SyntaxKind::Let, "let")]).into();
+        let decl = VarDeclNode::cast(node).unwrap();
+        assert!(decl.name_token().is_none());
+        assert!(decl.type_token().is_none());
+        assert!(decl.value_token().is_none());
+    }
+
+    #[test]
+    fn cast_rejects_other_kinds() {
+        let root: SyntaxNode = SyntaxNodeData::new(SyntaxKind::Root, vec![]).into();
+        assert!(VarDeclNode::cast(root).is_none());
+    }
+
+    #[test]
+    fn collects_every_string_literal_with_its_span() {
+        let source = "let greeting: string = \"hello\";\nlet farewell: string = \"bye\";";
+        let root = parse_tokens_to_cst(&table_lex(source));
+
+        let literals = collect_string_literals(&root);
+        assert_eq!(literals.len(), 2);
+        assert_eq!(literals[0].0, "hello");
+        assert_eq!(literals[1].0, "bye");
+        let (_, span) = literals[1];
+        assert_eq!(&source[span.start..span.end], "\"bye\"");
+    }
+
+    #[test]
+    fn string_literal_values_are_unescaped() {
+        let source = r#"let a = "a\nb\u{41}", b = "\"q\"";"#;
+        let literals = collect_string_literals(&parse_tokens_to_cst(&table_lex(source)));
+        let values: Vec<_> = literals.iter().map(|(value, _)| value.as_str()).collect();
+        assert_eq!(values, ["a\nbA", "\"q\""]);
+
+        // Not decoded twice when the lexer already did it.
+        let lexer = Lexer {
+            unescape_strings: true,
+            ..Lexer::default()
+        };
+        let source = r#"let a = "\\n";"#;
+        let literals = collect_string_literals(&parse_tokens_to_cst(&lexer.lex(source)));
+        assert_eq!(literals[0].0, "\\n");
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    DeclValue, SyntaxElement, SyntaxKind, SyntaxNode, Token, cst_to_source, lower_import,
+    lower_var_decl, parse_tokens_to_cst, table_lex,
+};
+
+/// Fixes only obvious whitespace problems — trailing whitespace, a missing
+/// space after `:`, and space before `;` — and copies every other byte of
+/// `source` unchanged, so the result differs from the input as little as
+/// possible.
+pub fn tidy(source: &str) -> String {
+    let tokens = table_lex(source);
+    let mut out = String::with_capacity(source.len());
+
+    for (i, tok) in tokens.iter().enumerate() {
+        let text = &source[tok.span.start..tok.span.end];
+        let prev = i.checked_sub(1).map(|j| tokens[j].kind);
+        let next = tokens.get(i + 1).map(|t| t.kind);
+
+        if tok.kind == SyntaxKind::Whitespace {
+            match next {
+                // Trailing whitespace; a `\r` belongs to the line ending.
+                None | Some(SyntaxKind::NewLine) => {
+                    if text.ends_with('\r') {
+                        out.push('\r');
+                    }
+                    continue;
+                }
+                // Space before `;`, unless it is the line's indentation.
+                Some(SyntaxKind::Semicolon)
+                    if !matches!(prev, None | Some(SyntaxKind::NewLine)) =>
+                {
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        if matches!(tok.kind, SyntaxKind::LineComment | SyntaxKind::DocComment) {
+            // A line comment runs to the line ending, so its tail is trailing whitespace.
+            out.push_str(text.trim_end());
+            if text.ends_with('\r') {
+                out.push('\r');
+            }
+            continue;
+        }
+
+        out.push_str(text);
+
+        if tok.kind == SyntaxKind::Colon
+            && !matches!(
+                next,
+                None | Some(SyntaxKind::Whitespace | SyntaxKind::NewLine)
+            )
+        {
+            out.push(' ');
+        }
+    }
+
+    out
+}
+
+/// Reformats `source` canonically: one statement per line, `name: type`,
+/// one space around `=`, and no space before `;`. Comments are kept, and
+/// runs of blank lines collapse to one. Statements that do not parse
+/// cleanly are copied unchanged. Formatting formatted output is a no-op.
+pub fn format_source(source: &str) -> String {
+    let root = parse_tokens_to_cst(&table_lex(source));
+    let mut out = String::new();
+    // Whether the last emitted line still needs its line break.
+    let mut line_open = false;
+    let mut blank_line = false;
+
+    let start_line = |out: &mut String, line_open: &mut bool, blank_line: &mut bool| {
+        if *line_open {
+            out.push('\n');
+            *line_open = false;
+        }
+        if *blank_line && !out.is_empty() {
+            out.push('\n');
+        }
+        *blank_line = false;
+    };
+
+    for child in &root.children {
+        match child {
+            SyntaxElement::Token(tok) => match tok.kind {
+                SyntaxKind::NewLine if line_open => {
+                    out.push('\n');
+                    line_open = false;
+                }
+                SyntaxKind::NewLine => blank_line = true,
+                SyntaxKind::LineComment | SyntaxKind::DocComment | SyntaxKind::BlockComment => {
+                    if line_open {
+                        out.push(' ');
+                    } else {
+                        start_line(&mut out, &mut line_open, &mut blank_line);
+                    }
+                    out.push_str(tok.text.trim_end());
+                    line_open = true;
+                }
+                _ => {}
+            },
+            SyntaxElement::Node(node) => {
+                start_line(&mut out, &mut line_open, &mut blank_line);
+                let text = format_statement(node).unwrap_or_else(|| cst_to_source(node));
+                out.push_str(text.trim_end());
+                line_open = true;
+                if text.ends_with('\n') {
+                    out.push('\n');
+                    line_open = false;
+                }
+            }
+        }
+    }
+    if line_open {
+        out.push('\n');
+    }
+    out
+}
+
+/// The canonical text of a well-formed statement, followed by its trailing
+/// comments and line break. `None` if the statement has to be kept as is.
+fn format_statement(node: &SyntaxNode) -> Option<String> {
+    let tokens = node.descendant_tokens();
+    let end = tokens
+        .iter()
+        .position(|t| t.kind == SyntaxKind::Semicolon)?;
+    let (body, trailing) = (&tokens[..end], &tokens[end + 1..]);
+    let significant: Vec<&Token> = body
+        .iter()
+        .copied()
+        .filter(|t| !t.kind.is_trivia())
+        .collect();
+    if significant.len() != body.iter().filter(|t| !is_blank(t.kind)).count() {
+        // A comment inside the statement has no canonical place.
+        return None;
+    }
+
+    let mut text = match node.kind() {
+        SyntaxKind::VarDecl => format_var_decl(node, &significant)?,
+        SyntaxKind::ImportStmt => {
+            let import = lower_import(node, SyntaxKind::Semicolon)?;
+            if significant
+                .iter()
+                .any(|t| t.kind == SyntaxKind::StringLiteral)
+            {
+                format!("use \"{}\";", import.path)
+            } else {
+                format!("use {};", import.path)
+            }
+        }
+        _ => return None,
+    };
+
+    for tok in trailing {
+        match tok.kind {
+            SyntaxKind::LineComment | SyntaxKind::BlockComment => {
+                text.push(' ');
+                text.push_str(tok.text.trim_end());
+            }
+            SyntaxKind::NewLine => text.push('\n'),
+            _ => {}
+        }
+    }
+    Some(text)
+}
+
+fn format_var_decl(node: &SyntaxNode, significant: &[&Token]) -> Option<String> {
+    // Every binding must be complete, or lowering would drop or repair it.
+    let well_formed = significant[1..]
+        .split(|t| t.kind == SyntaxKind::Comma)
+        .all(|binding| {
+            let kinds: Vec<_> = binding.iter().map(|t| t.kind).collect();
+            let value = |kind| {
+                matches!(
+                    kind,
+                    SyntaxKind::StringLiteral
+                        | SyntaxKind::IntLiteral
+                        | SyntaxKind::FloatLiteral
+                        | SyntaxKind::BoolLiteral
+                        | SyntaxKind::Ident
+                )
+            };
+            match kinds.as_slice() {
+                [SyntaxKind::Ident, SyntaxKind::Equal, v] => value(*v),
+                [
+                    SyntaxKind::Ident,
+                    SyntaxKind::Colon,
+                    SyntaxKind::Type,
+                    SyntaxKind::Equal,
+                    v,
+                ] => value(*v),
+                _ => false,
+            }
+        });
+    if !well_formed {
+        return None;
+    }
+
+    let bindings = lower_var_decl(node)
+        .into_iter()
+        .map(|decl| {
+            let value = match (decl.value, decl.value_kind) {
+                (DeclValue::Scalar(text), SyntaxKind::StringLiteral) => quote_string(&text),
+                (DeclValue::Scalar(text), _) => text,
+                // Arrays are kept as written.
+                (DeclValue::List(_), _) => return None,
+            };
+            Some(match decl.ty {
+                Some(ty) => format!("{}: {} = {}", decl.name, ty, value),
+                None => format!("{} = {}", decl.name, value),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("{} {};", significant[0].text, bindings.join(", ")))
+}
+
+/// Quotes a string value, with three quotes when a lone quote inside it
+/// would end a one-quote literal early.
+fn quote_string(value: &str) -> String {
+    let single = format!("\"{value}\"");
+    if table_lex(&single).len() == 1 {
+        single
+    } else {
+        format!("\"\"\"{value}\"\"\"")
+    }
+}
+
+fn is_blank(kind: SyntaxKind) -> bool {
+    matches!(kind, SyntaxKind::Whitespace | SyntaxKind::NewLine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_only_the_targeted_whitespace() {
+        assert_eq!(
+            tidy("let x:string = \"a\" ;  \n"),
+            "let x: string = \"a\";\n"
+        );
+    }
+
+    #[test]
+    fn leaves_everything_else_alone() {
+        let source = "let   x  :  string=\"a  b\";\n\n// note:here \n/* c ; */\n";
+        assert_eq!(
+            tidy(source),
+            "let   x  :  string=\"a  b\";\n\n// note:here\n/* c ; */\n"
+        );
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        assert_eq!(tidy("let x = \"a\"; \r\n"), "let x = \"a\";\r\n");
+        assert_eq!(tidy("// a \r\n"), "// a\r\n");
+    }
+
+    #[test]
+    fn tidy_output_is_stable() {
+        let once = tidy("let a:string = \"x\" ; \nlet b = \"y\"\t\n");
+        assert_eq!(tidy(&once), once);
+    }
+
+    #[test]
+    fn format_source_normalizes_declarations() {
+        let messy = "\n\nlet   x:string=\"hi\" ;let y = 1 ,z : float=2.5;  // note\n\n\n\n// next\nuse  a :: b ;\n";
+        assert_eq!(
+            format_source(messy),
+            "let x: string = \"hi\";\nlet y = 1, z: float = 2.5; // note\n\n// next\nuse a::b;\n"
+        );
+    }
+
+    #[test]
+    fn format_source_keeps_statements_it_cannot_format() {
+        let source = "let x /* why */ = \"a\";\nlet y = ;\nlet z=\"b\";";
+        assert_eq!(
+            format_source(source),
+            "let x /* why */ = \"a\";\nlet y = ;\nlet z = \"b\";\n"
+        );
+    }
+
+    #[test]
+    fn format_source_keeps_triple_quotes_when_needed() {
+        let source = "let a=\"\"\"say \"hi\" now\"\"\";\nlet b=\"\"\"plain\"\"\";";
+        assert_eq!(
+            format_source(source),
+            "let a = \"\"\"say \"hi\" now\"\"\";\nlet b = \"plain\";\n"
+        );
+    }
+
+    #[test]
+    fn format_source_keeps_doc_comments_before_their_statement() {
+        assert_eq!(
+            format_source("/// The answer.   \nlet x=42;"),
+            "/// The answer.\nlet x = 42;\n"
+        );
+    }
+
+    #[test]
+    fn format_source_is_idempotent() {
+        for source in [
+            "let   x:string=\"hi\" ;let y=\"z\"; /* c */\n\n\nlet q = 3 ;",
+            "// a\n\n/* b\n c */ let x=\"1\";\nuse \"p\" ;garbage ; let w = 2.0;",
+            "let x = \"unterminated",
+        ] {
+            let once = format_source(source);
+            assert_eq!(format_source(&once), once, "{source:?}");
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use crate::{table_lex, SyntaxKind};
+
+/// How tokens of one kind are rendered.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Style {
+    /// Foreground colour as `#rrggbb`.
+    pub color: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Style {
+    fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let hex = self.color.as_deref()?.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+/// Per-kind styles used by the HTML and ANSI exporters. Kinds without an
+/// entry are emitted unstyled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Theme {
+    pub styles: HashMap<SyntaxKind, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let style = |color: &str, bold: bool| Style {
+            color: Some(color.to_string()),
+            bold,
+            italic: false,
+        };
+        let comment = Style {
+            color: Some("#7f848e".to_string()),
+            bold: false,
+            italic: true,
+        };
+        Theme {
+            styles: HashMap::from([
+                (SyntaxKind::Let, style("#c678dd", true)),
+                (SyntaxKind::Const, style("#c678dd", true)),
+                (SyntaxKind::Use, style("#c678dd", true)),
+                (SyntaxKind::Type, style("#e5c07b", false)),
+                (SyntaxKind::Ident, style("#e06c75", false)),
+                (SyntaxKind::StringLiteral, style("#98c379", false)),
+                (SyntaxKind::IntLiteral, style("#d19a66", false)),
+                (SyntaxKind::FloatLiteral, style("#d19a66", false)),
+                (SyntaxKind::BoolLiteral, style("#d19a66", false)),
+                (SyntaxKind::LineComment, comment.clone()),
+                (SyntaxKind::DocComment, comment.clone()),
+                (SyntaxKind::BlockComment, comment),
+                (SyntaxKind::Error, style("#ff0000", true)),
+                (SyntaxKind::Unknown, style("#ff0000", true)),
+                (SyntaxKind::TokenLimit, style("#ff0000", true)),
+            ]),
+        }
+    }
+}
+
+impl Theme {
+    pub fn style(&self, kind: SyntaxKind) -> Option<&Style> {
+        self.styles.get(&kind)
+    }
+
+    /// Loads a theme from JSON such as `{"styles": {"Let": {"color": "#ff0000"}}}`.
+    /// Kinds named in the file replace the default style for that kind.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Theme, serde_json::Error> {
+        let overrides: Theme = serde_json::from_str(json)?;
+        let mut theme = Theme::default();
+        theme.styles.extend(overrides.styles);
+        Ok(theme)
+    }
+}
+
+/// Renders `source` as a `<pre>` block with one inline-styled `<span>` per
+/// styled token.
+pub fn highlight_html(source: &str, theme: &Theme) -> String {
+    let mut out = String::from("<pre>");
+    for tok in table_lex(source) {
+        let text = escape_html(&source[tok.span.start..tok.span.end]);
+        match theme.style(tok.kind) {
+            Some(style) => {
+                let mut css = Vec::new();
+                if let Some(color) = &style.color {
+                    css.push(format!("color:{color}"));
+                }
+                if style.bold {
+                    css.push("font-weight:bold".to_string());
+                }
+                if style.italic {
+                    css.push("font-style:italic".to_string());
+                }
+                out.push_str(&format!("<span style=\"{}\">{}</span>", css.join(";"), text));
+            }
+            None => out.push_str(&text),
+        }
+    }
+    out.push_str("</pre>");
+    out
+}
+
+/// Renders `source` with 24-bit ANSI colour escapes for terminals.
+pub fn highlight_ansi(source: &str, theme: &Theme) -> String {
+    let mut out = String::new();
+    for tok in table_lex(source) {
+        let text = &source[tok.span.start..tok.span.end];
+        let Some(style) = theme.style(tok.kind) else {
+            out.push_str(text);
+            continue;
+        };
+
+        let mut codes = Vec::new();
+        if style.bold {
+            codes.push("1".to_string());
+        }
+        if style.italic {
+            codes.push("3".to_string());
+        }
+        if let Some((r, g, b)) = style.rgb() {
+            codes.push(format!("38;2;{r};{g};{b}"));
+        }
+        if codes.is_empty() {
+            out.push_str(text);
+        } else {
+            out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text));
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_uses_the_theme_styles() {
+        let html = highlight_html("let x = \"<a>\";", &Theme::default());
+        assert!(html.starts_with("<pre><span style=\"color:#c678dd;font-weight:bold\">let</span>"));
+        assert!(html.contains("&quot;&lt;a&gt;&quot;"));
+    }
+
+    #[test]
+    fn ansi_wraps_styled_tokens_in_escapes() {
+        let ansi = highlight_ansi("let x", &Theme::default());
+        assert_eq!(
+            ansi,
+            "\x1b[1;38;2;198;120;221mlet\x1b[0m \x1b[38;2;224;108;117mx\x1b[0m"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn loaded_theme_overrides_the_keyword_color() {
+        let theme = Theme::from_json(r##"{"styles": {"Let": {"color": "#ff0000"}}}"##).unwrap();
+        assert_eq!(theme.style(SyntaxKind::Let).unwrap().color.as_deref(), Some("#ff0000"));
+        // Kinds the file does not mention keep their defaults.
+        assert_eq!(theme.style(SyntaxKind::Type), Theme::default().style(SyntaxKind::Type));
+
+        let html = highlight_html("let x", &theme);
+        assert!(html.contains("<span style=\"color:#ff0000\">let</span>"));
+        let ansi = highlight_ansi("let x", &theme);
+        assert!(ansi.starts_with("\x1b[38;2;255;0;0mlet\x1b[0m"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+/// A small integer standing for an interned string. Symbols from the same
+/// [`StringInterner`] are equal exactly when their strings are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Hands out one [`Symbol`] per distinct string, in first-seen order.
+#[derive(Debug, Clone, Default)]
+pub struct StringInterner {
+    symbols: HashMap<String, Symbol>,
+    strings: Vec<String>,
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(text.to_string());
+        self.symbols.insert(text.to_string(), symbol);
+        symbol
+    }
+
+    /// The string behind `symbol`. Panics if `symbol` came from another
+    /// interner that has handed out more symbols than this one.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_strings_share_a_symbol() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("alpha");
+        let b = interner.intern("beta");
+        assert_eq!(interner.intern("alpha"), a);
+        assert_ne!(a, b);
+        assert_eq!(interner.resolve(a), "alpha");
+        assert_eq!(interner.resolve(b), "beta");
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -1,6 +1,6 @@
 /// # Example
 ///```
-/// use rize_syntax::syntaxkind;
+/// use table_driven_lexer::syntaxkind;
 ///
 /// syntaxkind! {
 ///     Identifier,
@@ -15,12 +15,39 @@
 /// }
 ///
 /// assert_eq!(SyntaxKind::IndentWhiteSpace.to_string(), "INDENTWHITESPACE");
+/// assert_eq!("LPAREN".parse(), Ok(SyntaxKind::LParen));
+/// assert!("LParen".parse::<SyntaxKind>().is_err());
 ///
+/// // `FromStr` is the inverse of `Display`.
+/// for &kind in SyntaxKind::ALL {
+///     assert_eq!(kind.to_string().parse(), Ok(kind));
+/// }
+/// assert_eq!(SyntaxKind::count(), 9);
+/// ```
+///
+/// Variants may name the source text they are lexed from, which generates
+/// `SyntaxKind::from_keyword`:
+///
+///```
+/// use table_driven_lexer::syntaxkind;
+///
+/// syntaxkind! {
+///     Fn => "fn",
+///     Type => "i32" | "bool",
+///     Identifier,
+/// }
+///
+/// assert_eq!(SyntaxKind::from_keyword("fn"), Some(SyntaxKind::Fn));
+/// assert_eq!(SyntaxKind::from_keyword("bool"), Some(SyntaxKind::Type));
+/// assert_eq!(SyntaxKind::from_keyword("main"), None);
+/// assert_eq!(SyntaxKind::Type.spellings(), ["i32", "bool"]);
+/// assert!(SyntaxKind::Identifier.spellings().is_empty());
 /// ```
 #[macro_export]
 macro_rules! syntaxkind {
-    ( $( $variant:ident ),* $(,)? ) => {
+    ( $( #[$attr:meta] )* $( $variant:ident $( => $( $text:literal )|+ )? ),* $(,)? ) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $( #[$attr] )*
         #[repr(u8)]
         pub enum SyntaxKind {
             $( $variant ),*
@@ -33,14 +60,54 @@ macro_rules! syntaxkind {
                 }
             }
         }
+
+        impl std::str::FromStr for SyntaxKind {
+            type Err = String;
+
+            /// Parses the uppercase name printed by `Display`.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                SyntaxKind::ALL
+                    .iter()
+                    .copied()
+                    .find(|kind| kind.to_string() == s)
+                    .ok_or_else(|| format!("unknown syntax kind `{}`", s))
+            }
+        }
+
+        impl SyntaxKind {
+            /// Every variant, in declaration order.
+            pub const ALL: &'static [SyntaxKind] = &[ $( SyntaxKind::$variant ),* ];
+
+            /// The number of variants.
+            pub const fn count() -> usize {
+                SyntaxKind::ALL.len()
+            }
+
+            /// The texts this kind is lexed from as a keyword, in declaration
+            /// order; empty for a kind that names none.
+            pub fn spellings(&self) -> &'static [&'static str] {
+                match self {
+                    $( SyntaxKind::$variant => &[ $( $( $text ),+ )? ], )*
+                }
+            }
+
+            /// The kind a keyword is lexed as, or `None` for any other text.
+            pub fn from_keyword(text: &str) -> Option<SyntaxKind> {
+                match text {
+                    $( $( $( $text )|+ => Some(SyntaxKind::$variant), )? )*
+                    _ => None,
+                }
+            }
+        }
     };
 }
 
 syntaxkind! {
-    Let,
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    Let => "let",
     Ident,
     Colon,
-    Type,
+    Type => "string" | "int" | "float" | "list",
     Equal,
     StringLiteral,
     Semicolon,
@@ -53,5 +120,127 @@ syntaxkind! {
     EqualEqual,
     FatArrow,
     ColonEqual,
-    NewLine
+    NewLine,
+    Indent,
+    Dedent,
+    Comma,
+    IntLiteral,
+    FloatLiteral,
+    LineComment,
+    BlockComment,
+    Use => "use",
+    ImportStmt,
+    Const => "const",
+    Text,
+    Punctuation,
+    BoolLiteral => "true" | "false",
+    Value,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    ArrayValue,
+    DocComment,
+    Eof,
+    Unknown,
+    Dot,
+    Path,
+    TokenLimit
+}
+
+/// One exhaustive classification of [`SyntaxKind`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxCategory {
+    Keyword,
+    /// Names, including built-in type names.
+    Identifier,
+    Literal,
+    Punctuation,
+    Trivia,
+    /// Zero-width `Indent`/`Dedent` markers and the `Eof` sentinel.
+    Layout,
+    Error,
+    /// Kinds of tree nodes rather than tokens.
+    Node,
+}
+
+impl SyntaxKind {
+    pub fn category(&self) -> SyntaxCategory {
+        use SyntaxKind::*;
+        match self {
+            Let | Const | Use => SyntaxCategory::Keyword,
+            Ident | Type | Text => SyntaxCategory::Identifier,
+            StringLiteral | IntLiteral | FloatLiteral | BoolLiteral => SyntaxCategory::Literal,
+            Colon | Equal | Semicolon | DoubleColon | EqualLess | EqualEqual | FatArrow
+            | ColonEqual | Comma | Punctuation | LParen | RParen | LBrace | RBrace | LBracket
+            | RBracket | Dot => SyntaxCategory::Punctuation,
+            Whitespace | NewLine | LineComment | DocComment | BlockComment => {
+                SyntaxCategory::Trivia
+            }
+            Indent | Dedent | Eof => SyntaxCategory::Layout,
+            Error | Unknown | TokenLimit => SyntaxCategory::Error,
+            Root | VarDecl | ImportStmt | Value | ArrayValue | Path => SyntaxCategory::Node,
+        }
+    }
+
+    /// Kinds the parser skips between significant tokens.
+    pub fn is_trivia(&self) -> bool {
+        self.category() == SyntaxCategory::Trivia
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.category() == SyntaxCategory::Keyword
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trivia_kinds() {
+        assert!(SyntaxKind::Whitespace.is_trivia());
+        assert!(SyntaxKind::NewLine.is_trivia());
+        assert!(SyntaxKind::LineComment.is_trivia());
+        assert!(SyntaxKind::DocComment.is_trivia());
+        assert!(!SyntaxKind::Ident.is_trivia());
+        assert!(!SyntaxKind::Semicolon.is_trivia());
+    }
+
+    #[test]
+    fn keyword_kinds() {
+        assert!(SyntaxKind::Let.is_keyword());
+        assert!(SyntaxKind::Use.is_keyword());
+        assert!(SyntaxKind::Const.is_keyword());
+        assert!(!SyntaxKind::Type.is_keyword());
+        assert!(!SyntaxKind::Ident.is_keyword());
+    }
+
+    #[test]
+    fn every_kind_round_trips_through_its_name() {
+        for &kind in SyntaxKind::ALL {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+        assert_eq!("STRINGLITERAL".parse(), Ok(SyntaxKind::StringLiteral));
+        assert!("NOPE".parse::<SyntaxKind>().is_err());
+    }
+
+    #[test]
+    fn all_lists_every_variant_once_in_order() {
+        assert_eq!(SyntaxKind::count(), SyntaxKind::ALL.len());
+        for (i, &kind) in SyntaxKind::ALL.iter().enumerate() {
+            assert_eq!(kind as usize, i);
+        }
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(SyntaxKind::StringLiteral.category(), SyntaxCategory::Literal);
+        assert_eq!(SyntaxKind::Root.category(), SyntaxCategory::Node);
+        assert_eq!(SyntaxKind::Let.category(), SyntaxCategory::Keyword);
+        assert_eq!(SyntaxKind::DoubleColon.category(), SyntaxCategory::Punctuation);
+        assert_eq!(SyntaxKind::LineComment.category(), SyntaxCategory::Trivia);
+    }
 }
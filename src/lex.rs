@@ -1,15 +1,16 @@
-use std::char;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
-use std::iter::Peekable;
-use std::str::{self, Chars};
+use std::str::Utf8Error;
 use std::sync::Arc;
 
-use crate::SyntaxKind;
+use thiserror::Error;
+
+use crate::{Span, StringInterner, Symbol, SyntaxCategory, SyntaxKind};
 
 pub struct Spanned<T: Debug + Clone + PartialEq + Eq> {
     pub token: T,
-    pub offset: usize,
+    pub span: Span,
 }
 
 pub type Token = Arc<TokenData>;
@@ -17,7 +18,50 @@ pub type Token = Arc<TokenData>;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TokenData {
     pub kind: SyntaxKind,
-    pub text: String,
+    /// Borrowed for punctuation whose spelling is fixed, so those tokens
+    /// don't allocate.
+    pub text: Cow<'static, str>,
+    /// Byte range of the token in the lexed source.
+    pub span: Span,
+    /// Set on `Ident` tokens by [`Lexer::lex_interned`].
+    pub symbol: Option<Symbol>,
+    /// A string literal as written, quotes and escapes included. The lexer
+    /// sets it on every `StringLiteral`, so the token is printed back with
+    /// the quote it was lexed with, even after [`Lexer::unescape_strings`]
+    /// decoded its `text`.
+    pub raw: Option<String>,
+}
+
+/// Accessors that read the same as the [`SyntaxNodeData`](crate::SyntaxNodeData)
+/// ones, so code walking a tree can write `kind()` for tokens and nodes
+/// alike.
+pub trait TokenExt {
+    fn kind(&self) -> SyntaxKind;
+    fn text(&self) -> &str;
+}
+
+impl TokenExt for Token {
+    fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Compares only the kind, so `tok == SyntaxKind::Colon` reads like the
+/// check it is.
+impl PartialEq<SyntaxKind> for TokenData {
+    fn eq(&self, kind: &SyntaxKind) -> bool {
+        self.kind == *kind
+    }
+}
+
+impl PartialEq<SyntaxKind> for Token {
+    fn eq(&self, kind: &SyntaxKind) -> bool {
+        **self == *kind
+    }
 }
 
 impl Display for TokenData {
@@ -26,165 +70,1224 @@ impl Display for TokenData {
     }
 }
 
-pub type LexFn = fn(&mut Peekable<Chars>) -> Option<TokenData>;
+/// Character iterator over the source that knows its byte offset, so the
+/// tokenizers can record spans.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    source: &'a str,
+    offset: usize,
+}
 
-fn lex_equal(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    chars.next();
-    Some(TokenData {
-        kind: SyntaxKind::Equal,
-        text: "=".to_string(),
-    })
+impl<'a> Cursor<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Cursor { source, offset: 0 }
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The unconsumed remainder of the source.
+    pub fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    /// Consumes the next `len` bytes, which must end on a char boundary.
+    pub fn advance(&mut self, len: usize) -> &'a str {
+        let taken = &self.rest()[..len];
+        self.offset += len;
+        taken
+    }
+
+    /// Span from `start` up to the current offset.
+    pub fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.offset)
+    }
 }
 
-fn lex_colon(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    chars.next();
-    Some(TokenData {
-        kind: SyntaxKind::Colon,
-        text: ":".to_string(),
-    })
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
 }
 
-fn lex_semicolon(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    chars.next();
-    Some(TokenData {
-        kind: SyntaxKind::Semicolon,
-        text: ";".to_string(),
-    })
+/// A token as a kind and a byte range into the source it was lexed from.
+/// [`Lexer::lex_raw`] produces these without copying any text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawToken {
+    pub kind: SyntaxKind,
+    pub span: Span,
 }
 
-fn lex_newline(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    chars.next();
-    Some(TokenData {
-        kind: SyntaxKind::NewLine,
-        text: "\n".to_string(),
+impl RawToken {
+    /// The slice of `source` this token covers, quotes included for a
+    /// string literal.
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.span.start..self.span.end]
+    }
+
+    /// An owned [`Token`] with the same text `table_lex` would give it.
+    pub fn to_token(&self, source: &str) -> Token {
+        Token::new(self.token_data(source))
+    }
+
+    fn token_data(&self, source: &str) -> TokenData {
+        let raw = (self.kind == SyntaxKind::StringLiteral).then(|| self.text(source).to_string());
+        let text = match (fixed_text(self.kind), self.kind) {
+            (Some(fixed), _) => Cow::Borrowed(fixed),
+            (None, SyntaxKind::StringLiteral) => {
+                let quoted = self.text(source);
+                let quote = quoted.chars().next().unwrap_or('"');
+                let width = if is_triple_quoted(quoted, quote) { 3 } else { 1 };
+                let mut value = quoted.chars();
+                for _ in 0..width {
+                    value.next();
+                    value.next_back();
+                }
+                value.as_str().to_string().into()
+            }
+            (None, _) => self.text(source).to_string().into(),
+        };
+        TokenData {
+            kind: self.kind,
+            text,
+            span: self.span,
+            symbol: None,
+            raw,
+        }
+    }
+}
+
+pub type LexFn = fn(&mut Cursor) -> Option<RawToken>;
+
+/// The only possible text of a fixed-spelling token kind.
+pub(crate) fn fixed_text(kind: SyntaxKind) -> Option<&'static str> {
+    match kind {
+        SyntaxKind::Equal => Some("="),
+        SyntaxKind::Colon => Some(":"),
+        SyntaxKind::DoubleColon => Some("::"),
+        SyntaxKind::Semicolon => Some(";"),
+        SyntaxKind::NewLine => Some("\n"),
+        SyntaxKind::Comma => Some(","),
+        SyntaxKind::LParen => Some("("),
+        SyntaxKind::RParen => Some(")"),
+        SyntaxKind::LBrace => Some("{"),
+        SyntaxKind::RBrace => Some("}"),
+        SyntaxKind::LBracket => Some("["),
+        SyntaxKind::RBracket => Some("]"),
+        SyntaxKind::Dot => Some("."),
+        _ => None,
+    }
+}
+
+fn raw(cursor: &Cursor, kind: SyntaxKind, start: usize) -> Option<RawToken> {
+    Some(RawToken {
+        kind,
+        span: cursor.span_from(start),
     })
 }
 
+fn lex_single(cursor: &mut Cursor, kind: SyntaxKind) -> Option<RawToken> {
+    let start = cursor.offset();
+    cursor.next()?;
+    raw(cursor, kind, start)
+}
+
+fn lex_equal(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::Equal)
+}
+
+fn lex_colon(cursor: &mut Cursor) -> Option<RawToken> {
+    if !cursor.rest().starts_with("::") {
+        return lex_single(cursor, SyntaxKind::Colon);
+    }
+    let start = cursor.offset();
+    cursor.advance(2);
+    raw(cursor, SyntaxKind::DoubleColon, start)
+}
+
+fn lex_semicolon(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::Semicolon)
+}
+
+fn lex_newline(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::NewLine)
+}
+
+fn lex_comma(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::Comma)
+}
+
+fn lex_dot(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::Dot)
+}
+
+fn lex_lparen(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::LParen)
+}
+
+fn lex_rparen(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::RParen)
+}
+
+fn lex_lbrace(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::LBrace)
+}
+
+fn lex_rbrace(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::RBrace)
+}
+
+fn lex_lbracket(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::LBracket)
+}
+
+fn lex_rbracket(cursor: &mut Cursor) -> Option<RawToken> {
+    lex_single(cursor, SyntaxKind::RBracket)
+}
+
 fn punctuation_tokenizers() -> HashMap<char, LexFn> {
     HashMap::from([
         ('=', lex_equal as LexFn),
         (':', lex_colon),
         (';', lex_semicolon),
         ('\n', lex_newline),
+        (',', lex_comma),
+        ('(', lex_lparen),
+        (')', lex_rparen),
+        ('{', lex_lbrace),
+        ('}', lex_rbrace),
+        ('[', lex_lbracket),
+        (']', lex_rbracket),
+        ('.', lex_dot),
     ])
 }
 
-fn lex_whitespace(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    if chars
-        .peek()
-        .copied()
-        .map(|c| c.is_whitespace() && c != '\n')
-        != Some(true)
-    {
+fn lex_whitespace(cursor: &mut Cursor) -> Option<RawToken> {
+    let start = cursor.offset();
+    let text = take_while(cursor, |c| c.is_whitespace() && c != '\n');
+    if text.is_empty() {
+        None
+    } else {
+        raw(cursor, SyntaxKind::Whitespace, start)
+    }
+}
+
+fn lex_ident_or_keyword(cursor: &mut Cursor) -> Option<RawToken> {
+    if cursor.peek().map(|c| c.is_alphabetic()) != Some(true) {
         return None;
     }
-    let mut text = String::new();
-    while let Some(&c) = chars.peek() {
-        if c.is_whitespace() && c != '\n' {
-            text.push(c);
-            chars.next();
-        } else {
-            break;
+    let start = cursor.offset();
+    let text = take_while(cursor, |c| c.is_alphanumeric() || c == '_');
+    let kind = SyntaxKind::from_keyword(text).unwrap_or(SyntaxKind::Ident);
+    raw(cursor, kind, start)
+}
+
+/// `// ...` up to (not including) the newline, or `/* ... */`. An
+/// unterminated block comment becomes an `Error` token.
+fn lex_comment(cursor: &mut Cursor) -> Option<RawToken> {
+    let start = cursor.offset();
+    let rest = cursor.rest();
+    let (kind, len) = if rest.starts_with("//") {
+        (SyntaxKind::LineComment, rest.find('\n').unwrap_or(rest.len()))
+    } else if let Some(body) = rest.strip_prefix("/*") {
+        match body.find("*/") {
+            Some(end) => (SyntaxKind::BlockComment, end + 4),
+            None => (SyntaxKind::Error, rest.len()),
         }
+    } else {
+        return None;
+    };
+
+    cursor.advance(len);
+    raw(cursor, kind, start)
+}
+
+/// `///` doc comments, which run to the end of the line like `//` ones.
+/// Four or more slashes make a plain `LineComment` again, so `////` rulers
+/// are not documentation; anything else starting with `//` is left to
+/// [`lex_comment`].
+fn lex_doc_comment(cursor: &mut Cursor) -> Option<RawToken> {
+    let rest = cursor.rest();
+    if !rest.starts_with("///") || rest.starts_with("////") {
+        return None;
     }
-    Some(TokenData {
-        kind: SyntaxKind::Whitespace,
-        text,
-    })
+    let start = cursor.offset();
+    cursor.advance(rest.find('\n').unwrap_or(rest.len()));
+    raw(cursor, SyntaxKind::DocComment, start)
 }
 
-fn lex_ident_or_keyword(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    let mut text = String::new();
-    if chars.peek().copied().map(|c| c.is_alphabetic()) != Some(true) {
+/// Decimal integers (`42`) and floats (`4.2`). A `.` only belongs to the
+/// number when a digit follows it.
+///
+/// Digits may be grouped with `_`, which stays in the token text. Every `_`
+/// must sit between two digits, so `1__0` and `1_` lex as a single `Error`
+/// token. A leading `_` never starts a number.
+///
+/// Integers may also be written in hex (`0x1F`), octal (`0o17`) or binary
+/// (`0b1010`), prefix kept in the text. A digit the base doesn't allow turns
+/// the whole literal into an `Error` token.
+fn lex_number(cursor: &mut Cursor) -> Option<RawToken> {
+    if cursor.peek().map(|c| c.is_ascii_digit()) != Some(true) {
         return None;
     }
-    while let Some(&c) = chars.peek() {
-        if c.is_alphanumeric() || c == '_' {
-            text.push(c);
-            chars.next();
-        } else {
-            break;
-        }
+    let start = cursor.offset();
+    if let Some(radix) = radix_prefix(cursor.rest()) {
+        cursor.advance(2);
+        let digits = take_while(cursor, |c| c.is_ascii_alphanumeric() || c == '_');
+        let kind = match invalid_digit(digits, radix) {
+            None => SyntaxKind::IntLiteral,
+            Some(_) => SyntaxKind::Error,
+        };
+        return raw(cursor, kind, start);
     }
-    let kind = match text.as_str() {
-        "let" => SyntaxKind::Let,
-        "string" => SyntaxKind::Type,
-        _ => SyntaxKind::Ident,
+
+    let is_digit = |c: char| c.is_ascii_digit() || c == '_';
+    take_while(cursor, is_digit);
+    let mut kind = SyntaxKind::IntLiteral;
+
+    let mut rest = cursor.rest().chars();
+    if rest.next() == Some('.') && rest.next().is_some_and(|c| c.is_ascii_digit()) {
+        cursor.next();
+        take_while(cursor, is_digit);
+        kind = SyntaxKind::FloatLiteral;
+    }
+
+    let bytes = &cursor.source.as_bytes()[start..cursor.offset()];
+    let separated = |i: usize| {
+        i > 0 && bytes[i - 1].is_ascii_digit() && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
     };
-    Some(TokenData { kind, text })
+    if !(0..bytes.len()).all(|i| bytes[i] != b'_' || separated(i)) {
+        kind = SyntaxKind::Error;
+    }
+    raw(cursor, kind, start)
+}
+
+/// The base announced by a `0x`, `0o` or `0b` prefix.
+pub(crate) fn radix_prefix(text: &str) -> Option<u32> {
+    match text.get(..2)? {
+        "0x" => Some(16),
+        "0o" => Some(8),
+        "0b" => Some(2),
+        _ => None,
+    }
 }
 
-fn lex_string_literal(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    if chars.peek() != Some(&'"') {
+/// Why the digits after a radix prefix don't form a literal: the first
+/// character the base doesn't allow, or `None` inside `Some` when the
+/// digits are missing or a `_` isn't between two of them.
+fn invalid_digit(digits: &str, radix: u32) -> Option<Option<char>> {
+    if let Some(bad) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+        return Some(Some(bad));
+    }
+    let well_formed = !digits.is_empty()
+        && !digits.starts_with('_')
+        && !digits.ends_with('_')
+        && !digits.contains("__");
+    (!well_formed).then_some(None)
+}
+
+/// Whether a string literal's source text opens with three quotes. A
+/// one-quote literal can't: its second quote would have closed it.
+fn is_triple_quoted(text: &str, quote: char) -> bool {
+    text.len() >= 6 && text.chars().take(3).all(|c| c == quote)
+}
+
+/// A terminated literal's span covers both quotes. An unterminated one
+/// becomes an `Error` token running to the end of input, opening quote
+/// included. Escapes are kept as written; `\` only stops the next
+/// character from closing the literal. A `\u{...}` escape must hold 1 to 6
+/// hex digits naming a Unicode scalar value, or the whole literal is an
+/// `Error` token; [`unescape_unicode`] decodes them.
+///
+/// Three quotes open a literal that only three quotes close, so it can hold
+/// lone quotes as well as newlines.
+fn lex_string_literal(cursor: &mut Cursor, quote: char) -> Option<RawToken> {
+    if cursor.peek() != Some(quote) {
         return None;
     }
-    chars.next(); // consume the opening quote
-    let mut value = String::new();
-    while let Some(&c) = chars.peek() {
-        chars.next();
-        if c == '"' {
-            return Some(TokenData {
-                kind: SyntaxKind::StringLiteral,
-                text: value,
+    let start = cursor.offset();
+    let kind = match scan_string(cursor, quote) {
+        (true, None) => SyntaxKind::StringLiteral,
+        _ => SyntaxKind::Error,
+    };
+    raw(cursor, kind, start)
+}
+
+/// Consumes the string literal at the cursor. Returns whether it was
+/// closed, and the first `\u` escape in it that does not decode, with its
+/// byte range relative to the opening quote.
+fn scan_string(
+    cursor: &mut Cursor,
+    quote: char,
+) -> (bool, Option<(std::ops::Range<usize>, EscapeError)>) {
+    let start = cursor.offset();
+    let triple = quote.to_string().repeat(3);
+    let close = if cursor.rest().starts_with(&triple) {
+        triple
+    } else {
+        quote.to_string()
+    };
+    cursor.advance(close.len());
+
+    let mut bad_escape = None;
+    while !cursor.rest().starts_with(&close) {
+        match cursor.next() {
+            Some('\\') if cursor.peek() == Some('u') => {
+                let at = cursor.offset() - 1 - start;
+                cursor.next();
+                match decode_unicode_escape(cursor.rest()) {
+                    Ok((_, len)) => {
+                        cursor.advance(len);
+                    }
+                    Err(reason) => {
+                        let end = cursor.offset() - start + braced_len(cursor.rest());
+                        bad_escape = bad_escape.or(Some((at..end, reason)));
+                    }
+                }
+            }
+            Some('\\') => {
+                cursor.next();
+            }
+            Some(_) => {}
+            None => return (false, bad_escape),
+        }
+    }
+    cursor.advance(close.len());
+    (true, bad_escape)
+}
+
+/// Why a `\u{...}` escape does not decode.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EscapeError {
+    #[error("expected 1 to 6 hex digits in braces after `\\u`")]
+    Malformed,
+    #[error("more than 6 hex digits")]
+    TooLong,
+    #[error("`{0:X}` is not a Unicode scalar value")]
+    OutOfRange(u32),
+}
+
+/// Decodes the `{...}` part of a `\u{...}` escape at the start of `rest`,
+/// returning the character and the length of the braced part.
+fn decode_unicode_escape(rest: &str) -> Result<(char, usize), EscapeError> {
+    let body = rest.strip_prefix('{').ok_or(EscapeError::Malformed)?;
+    let len = body
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(body.len());
+    if len == 0 || !body[len..].starts_with('}') {
+        return Err(EscapeError::Malformed);
+    }
+    if len > 6 {
+        return Err(EscapeError::TooLong);
+    }
+    let code = u32::from_str_radix(&body[..len], 16).expect("at most six hex digits");
+    let c = char::from_u32(code).ok_or(EscapeError::OutOfRange(code))?;
+    Ok((c, len + 2))
+}
+
+/// The length of a `{...}` group of letters and digits at the start of
+/// `rest`, or 0 if there is none.
+fn braced_len(rest: &str) -> usize {
+    let Some(body) = rest.strip_prefix('{') else {
+        return 0;
+    };
+    let len = body
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(body.len());
+    if body[len..].starts_with('}') {
+        len + 2
+    } else {
+        0
+    }
+}
+
+/// Decodes the `\u{...}` escapes in the text of a `StringLiteral` token.
+/// Other escapes are kept as written.
+pub fn unescape_unicode(value: &str) -> Result<String, EscapeError> {
+    decode_escapes(value, false)
+}
+
+/// Decodes `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'` and `\u{...}` in the
+/// text of a `StringLiteral` token. Other escapes are kept as written.
+pub fn unescape(value: &str) -> Result<String, EscapeError> {
+    decode_escapes(value, true)
+}
+
+/// Decodes `\u{...}` escapes, and the single-character ones too if
+/// `all` is set.
+fn decode_escapes(value: &str, all: bool) -> Result<String, EscapeError> {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some((_, 'u')) => {
+                let (decoded, len) = decode_unicode_escape(&value[i + 2..])?;
+                out.push(decoded);
+                for _ in 0..len {
+                    chars.next();
+                }
+            }
+            Some((_, next)) => match (all, next) {
+                (true, 'n') => out.push('\n'),
+                (true, 'r') => out.push('\r'),
+                (true, 't') => out.push('\t'),
+                (true, '0') => out.push('\0'),
+                (true, '\\' | '"' | '\'') => out.push(next),
+                _ => {
+                    out.push('\\');
+                    out.push(next);
+                }
+            },
+            None => out.push('\\'),
+        }
+    }
+    Ok(out)
+}
+
+/// How the lexer reports changes in leading indentation (the off-side rule).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndentMode {
+    /// Leading whitespace is ordinary `Whitespace` trivia.
+    #[default]
+    Off,
+    /// Every physical line is measured, including blank lines.
+    Physical,
+    /// Only the start of a logical line is measured. Blank lines and lines
+    /// continuing a statement (the previous line ended in an operator such
+    /// as `=` or `:`) never open or close a block.
+    Logical,
+}
+
+/// Configuration for [`table_lex`].
+#[derive(Debug, Clone)]
+pub struct Lexer {
+    pub indent_mode: IndentMode,
+    /// Opens and closes string literals. A backslash before it keeps a
+    /// literal open.
+    pub string_quote: char,
+    /// Appends a zero-width `Eof` token at the end of the source.
+    pub eof: bool,
+    /// Matches keywords, type names and `true`/`false` in any case, so
+    /// `LET` lexes as `Let`. The token text keeps the source casing.
+    pub case_insensitive_keywords: bool,
+    /// Decodes the escapes in string literal text with [`unescape`]. The
+    /// literal as written, which the formatter and `cst_to_source` print,
+    /// stays in `raw`.
+    pub unescape_strings: bool,
+    /// Stops lexing after this many tokens, so untrusted input cannot grow
+    /// the token list without bound. A zero-width `TokenLimit` token marks
+    /// where lexing stopped, and [`lex_errors`] reports it as
+    /// [`LexError::TokenLimit`]. The rest of the source gets no tokens.
+    /// Layout tokens added afterwards don't count.
+    pub max_tokens: Option<usize>,
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Lexer {
+            indent_mode: IndentMode::default(),
+            string_quote: '"',
+            eof: false,
+            case_insensitive_keywords: false,
+            unescape_strings: false,
+            max_tokens: None,
+        }
+    }
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lex(&self, source: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.lex_into(source, &mut tokens);
+        tokens
+    }
+
+    /// Like [`Lexer::lex`], but replaces the contents of `out`, so a caller
+    /// lexing many snippets can reuse one buffer.
+    pub fn lex_into(&self, source: &str, out: &mut Vec<Token>) {
+        out.clear();
+        out.extend(
+            self.lex_raw(source)
+                .iter()
+                .map(|tok| Token::new(self.token_data(tok, source))),
+        );
+    }
+
+    /// Like [`Lexer::lex`], but every `Ident` token also carries its
+    /// [`Symbol`] in `interner`.
+    pub fn lex_interned(&self, source: &str, interner: &mut StringInterner) -> Vec<Token> {
+        self.lex_raw(source)
+            .iter()
+            .map(|tok| {
+                let mut data = self.token_data(tok, source);
+                if data.kind == SyntaxKind::Ident {
+                    data.symbol = Some(interner.intern(&data.text));
+                }
+                Token::new(data)
+            })
+            .collect()
+    }
+
+    /// The owned form of `tok`, with its escapes decoded if
+    /// `unescape_strings` is set.
+    fn token_data(&self, tok: &RawToken, source: &str) -> TokenData {
+        let mut data = tok.token_data(source);
+        if self.unescape_strings
+            && data.kind == SyntaxKind::StringLiteral
+            && let Ok(value) = unescape(&data.text)
+        {
+            data.text = value.into();
+        }
+        data
+    }
+
+    /// Like [`Lexer::lex`], but the tokens only record where their text
+    /// is, so nothing is copied out of `source`.
+    pub fn lex_raw(&self, source: &str) -> Vec<RawToken> {
+        let mut tokens = Vec::new();
+        let mut cursor = Cursor::new(source);
+        let punct = punctuation_tokenizers();
+
+        while cursor.peek().is_some() {
+            if self.max_tokens.is_some_and(|max| tokens.len() >= max) {
+                tokens.push(RawToken {
+                    kind: SyntaxKind::TokenLimit,
+                    span: cursor.span_from(cursor.offset()),
+                });
+                break;
+            }
+            let mut tok = lex_one(&mut cursor, &punct, self.string_quote);
+            if self.case_insensitive_keywords && tok.kind == SyntaxKind::Ident {
+                let text = tok.text(source).to_lowercase();
+                tok.kind = SyntaxKind::from_keyword(&text).unwrap_or(SyntaxKind::Ident);
+            }
+            tokens.push(tok);
+        }
+
+        let mut tokens = match self.indent_mode {
+            IndentMode::Off => tokens,
+            mode => insert_indentation(tokens, mode, source),
+        };
+        if self.eof {
+            tokens.push(RawToken {
+                kind: SyntaxKind::Eof,
+                span: Span::new(source.len(), source.len()),
             });
         }
-        value.push(c);
+        tokens
     }
-    // Unterminated string literal
-    Some(TokenData {
-        kind: SyntaxKind::Error,
-        text: value,
-    })
+}
+
+/// Lexes the token at the cursor, which must not be at the end of input.
+/// The result depends only on the text from the cursor onwards.
+fn lex_one(cursor: &mut Cursor, punct: &HashMap<char, LexFn>, quote: char) -> RawToken {
+    if let Some(&lex_fn) = cursor.peek().and_then(|ch| punct.get(&ch))
+        && let Some(tok) = lex_fn(cursor)
+    {
+        return tok;
+    }
+
+    let tok = lex_doc_comment(cursor)
+        .or_else(|| lex_comment(cursor))
+        .or_else(|| lex_whitespace(cursor))
+        .or_else(|| lex_ident_or_keyword(cursor))
+        .or_else(|| lex_number(cursor))
+        .or_else(|| lex_string_literal(cursor, quote));
+    if let Some(tok) = tok {
+        return tok;
+    }
+
+    // fallback: unknown character
+    let start = cursor.offset();
+    cursor.next(); // consume one char
+    RawToken {
+        kind: SyntaxKind::Unknown,
+        span: cursor.span_from(start),
+    }
+}
+
+/// A problem the lexer recorded as an `Error` token.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "miette", derive(miette::Diagnostic))]
+pub enum LexError {
+    #[error("unexpected character `{text}`")]
+    UnexpectedChar {
+        text: String,
+        #[cfg_attr(feature = "miette", label("not valid here"))]
+        span: Span,
+    },
+    #[error("unterminated string literal")]
+    UnterminatedString {
+        #[cfg_attr(feature = "miette", label("string starts here"))]
+        span: Span,
+    },
+    #[error("unterminated block comment")]
+    UnterminatedComment {
+        #[cfg_attr(feature = "miette", label("comment starts here"))]
+        span: Span,
+    },
+    #[error("invalid number literal `{text}`")]
+    InvalidNumber {
+        text: String,
+        #[cfg_attr(feature = "miette", label("not a valid number"))]
+        span: Span,
+    },
+    #[error("invalid digit `{digit}` in a base-{radix} literal")]
+    InvalidDigit {
+        digit: char,
+        radix: u32,
+        #[cfg_attr(feature = "miette", label("not a valid number"))]
+        span: Span,
+    },
+    #[error("invalid unicode escape: {reason}")]
+    InvalidEscape {
+        reason: EscapeError,
+        #[cfg_attr(feature = "miette", label("invalid escape"))]
+        span: Span,
+    },
+    /// [`Lexer::max_tokens`] was reached. The span is empty and marks
+    /// where lexing stopped; the rest of the source has no tokens.
+    #[error("too many tokens; the rest of the input was not lexed")]
+    TokenLimit {
+        #[cfg_attr(feature = "miette", label("lexing stopped here"))]
+        span: Span,
+    },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar { span, .. }
+            | LexError::UnterminatedString { span }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidNumber { span, .. }
+            | LexError::InvalidDigit { span, .. }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::TokenLimit { span } => *span,
+        }
+    }
+}
+
+/// The errors behind the `Error`, `Unknown` and `TokenLimit` tokens of a
+/// token stream, in order, for tokens from [`table_lex`].
+pub fn lex_errors(tokens: &[Token]) -> Vec<LexError> {
+    Lexer::default().lex_errors(tokens)
+}
+
+impl Lexer {
+    /// Like [`lex_errors`], for tokens this lexer produced.
+    pub fn lex_errors(&self, tokens: &[Token]) -> Vec<LexError> {
+        let quote = self.string_quote;
+        tokens
+            .iter()
+            .filter(|t| t.kind.category() == SyntaxCategory::Error)
+            .map(|t| {
+                let span = t.span;
+                if t.kind == SyntaxKind::Unknown {
+                    LexError::UnexpectedChar {
+                        text: t.text.to_string(),
+                        span,
+                    }
+                } else if t.kind == SyntaxKind::TokenLimit {
+                    LexError::TokenLimit { span }
+                } else if t.text.starts_with(quote) {
+                    match scan_string(&mut Cursor::new(&t.text), quote) {
+                        (true, Some((range, reason))) => LexError::InvalidEscape {
+                            reason,
+                            span: Span::new(span.start + range.start, span.start + range.end),
+                        },
+                        _ => LexError::UnterminatedString { span },
+                    }
+                } else if t.text.starts_with("/*") {
+                    LexError::UnterminatedComment { span }
+                } else if t.text.starts_with(|c: char| c.is_ascii_digit()) {
+                    let bad_digit = radix_prefix(&t.text)
+                        .and_then(|radix| Some((invalid_digit(&t.text[2..], radix)??, radix)));
+                    match bad_digit {
+                        Some((digit, radix)) => LexError::InvalidDigit { digit, radix, span },
+                        None => LexError::InvalidNumber {
+                            text: t.text.to_string(),
+                            span,
+                        },
+                    }
+                } else {
+                    LexError::UnexpectedChar {
+                        text: t.text.to_string(),
+                        span,
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// A delimiter that [`check_balanced`] found out of place.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "miette", derive(miette::Diagnostic))]
+pub enum UnbalancedError {
+    #[error("unmatched closing `{text}`")]
+    Unmatched {
+        text: &'static str,
+        #[cfg_attr(feature = "miette", label("nothing to close"))]
+        span: Span,
+    },
+    #[error("`{open}` closed by `{close}`")]
+    Mismatched {
+        open: &'static str,
+        close: &'static str,
+        #[cfg_attr(feature = "miette", label("expected `{}`", closing(open)))]
+        span: Span,
+    },
+    #[error("unclosed `{text}`")]
+    Unclosed {
+        text: &'static str,
+        #[cfg_attr(feature = "miette", label("opened here"))]
+        span: Span,
+    },
+}
+
+impl UnbalancedError {
+    /// Byte offset of the offending delimiter.
+    pub fn offset(&self) -> usize {
+        match self {
+            UnbalancedError::Unmatched { span, .. }
+            | UnbalancedError::Mismatched { span, .. }
+            | UnbalancedError::Unclosed { span, .. } => span.start,
+        }
+    }
+}
+
+/// The closing delimiter kind for an opening one.
+fn closer(kind: SyntaxKind) -> Option<SyntaxKind> {
+    match kind {
+        SyntaxKind::LParen => Some(SyntaxKind::RParen),
+        SyntaxKind::LBrace => Some(SyntaxKind::RBrace),
+        SyntaxKind::LBracket => Some(SyntaxKind::RBracket),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "miette")]
+fn closing(open: &str) -> &'static str {
+    match open {
+        "(" => ")",
+        "{" => "}",
+        _ => "]",
+    }
+}
+
+/// Checks that parentheses, braces and brackets nest properly, reporting
+/// the first delimiter that breaks the nesting. A closer of the wrong kind
+/// is a mismatch; openers left at the end are reported from the innermost.
+pub fn check_balanced(tokens: &[Token]) -> Result<(), UnbalancedError> {
+    let mut open: Vec<&Token> = Vec::new();
+    for tok in tokens {
+        let kind = tok.kind;
+        if closer(kind).is_some() {
+            open.push(tok);
+        } else if matches!(
+            kind,
+            SyntaxKind::RParen | SyntaxKind::RBrace | SyntaxKind::RBracket
+        ) {
+            let text = fixed_text(kind).unwrap_or_default();
+            match open.pop() {
+                None => return Err(UnbalancedError::Unmatched { text, span: tok.span }),
+                Some(opener) if closer(opener.kind) != Some(kind) => {
+                    return Err(UnbalancedError::Mismatched {
+                        open: fixed_text(opener.kind).unwrap_or_default(),
+                        close: text,
+                        span: tok.span,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    match open.pop() {
+        Some(opener) => Err(UnbalancedError::Unclosed {
+            text: fixed_text(opener.kind).unwrap_or_default(),
+            span: opener.span,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Coalesces each run of adjacent `Error` and `Unknown` tokens into one
+/// token with their concatenated text and combined span, so `@@@` is
+/// reported once. The merged token is `Unknown` if every token in the run
+/// was, `Error` otherwise.
+pub fn merge_adjacent_errors(tokens: Vec<Token>) -> Vec<Token> {
+    let is_error = |kind| matches!(kind, SyntaxKind::Error | SyntaxKind::Unknown);
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    for tok in tokens {
+        match out.last_mut() {
+            Some(prev)
+                if is_error(prev.kind) && is_error(tok.kind) && prev.span.end == tok.span.start =>
+            {
+                let kind = if prev.kind == tok.kind {
+                    tok.kind
+                } else {
+                    SyntaxKind::Error
+                };
+                *prev = Token::new(TokenData {
+                    kind,
+                    text: format!("{}{}", prev.text, tok.text).into(),
+                    span: prev.span.merge(tok.span),
+                    symbol: None,
+                    raw: None,
+                });
+            }
+            _ => out.push(tok),
+        }
+    }
+    out
 }
 
 pub fn table_lex(source: &str) -> Vec<Token> {
     let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
+    lex_into(source, &mut tokens);
+    tokens
+}
+
+/// Lexes `source` like [`table_lex`], one token at a time, pairing each
+/// token with its span.
+pub fn lex_spanned_iter(source: &str) -> impl Iterator<Item = (Token, Span)> + '_ {
+    let mut cursor = Cursor::new(source);
+    let punct = punctuation_tokenizers();
+    let quote = Lexer::default().string_quote;
+    std::iter::from_fn(move || {
+        cursor.peek()?;
+        let tok = lex_one(&mut cursor, &punct, quote).to_token(source);
+        let span = tok.span;
+        Some((tok, span))
+    })
+}
+
+/// [`table_lex`] into a caller-provided buffer, which is cleared first.
+pub fn lex_into(source: &str, out: &mut Vec<Token>) {
+    Lexer::default().lex_into(source, out)
+}
+
+/// Concatenates the source text of `tokens`. For the output of
+/// [`table_lex`], which keeps every character, this is the original source.
+pub fn tokens_to_string(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for tok in tokens {
+        push_source(&mut out, tok);
+    }
+    out
+}
+
+/// Appends the source `tok` was lexed from.
+pub(crate) fn push_source(out: &mut String, tok: &TokenData) {
+    if let Some(raw) = &tok.raw {
+        out.push_str(raw);
+        return;
+    }
+    if tok.kind != SyntaxKind::StringLiteral {
+        out.push_str(&tok.text);
+        return;
+    }
+    // String literal tokens hold the unquoted value; the span tells one
+    // quote from three.
+    let width = tok.span.len().saturating_sub(tok.text.len());
+    let quotes = if width == 6 { "\"\"\"" } else { "\"" };
+    out.push_str(quotes);
+    out.push_str(&tok.text);
+    out.push_str(quotes);
+}
+
+/// How many quote characters open (and close) a string literal token.
+fn quote_width(tok: &TokenData) -> usize {
+    match &tok.raw {
+        Some(raw) => raw.chars().next().map_or(0, |q| if is_triple_quoted(raw, q) { 3 } else { 1 }),
+        None => tok.span.len().saturating_sub(tok.text.len()) / 2,
+    }
+}
+
+/// The span of a string literal's contents, between its quotes.
+pub(crate) fn string_contents_span(tok: &TokenData) -> Span {
+    let width = quote_width(tok);
+    Span::new(tok.span.start + width, tok.span.end - width)
+}
+
+/// A string literal's contents as written, between its quotes. Differs
+/// from `text` only once the lexer has decoded the escapes.
+pub(crate) fn string_contents(tok: &TokenData) -> &str {
+    match &tok.raw {
+        Some(raw) => {
+            let width = quote_width(tok);
+            &raw[width..raw.len() - width]
+        }
+        None => &tok.text,
+    }
+}
+
+/// [`table_lex`] for source that arrives as bytes. The bytes are checked
+/// once and lexed in place, without copying them into a `String`.
+pub fn lex_bytes(source: &[u8]) -> Result<Vec<Token>, Utf8Error> {
+    std::str::from_utf8(source).map(table_lex)
+}
+
+/// [`table_lex`] without the trivia: no whitespace, newline or comment
+/// tokens are built at all. Spans still point into `source`.
+pub fn lex_significant(source: &str) -> Vec<Token> {
+    Lexer::default()
+        .lex_raw(source)
+        .iter()
+        .filter(|tok| !tok.kind.is_trivia())
+        .map(|tok| tok.to_token(source))
+        .collect()
+}
+
+/// A replacement of the bytes at `span` in the old source by `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+/// Updates `old_tokens`, the [`table_lex`] output for the text before
+/// `edit`, to match `source`, the text after it. Lexing restarts at the
+/// line the edit touches and stops as soon as a token boundary lines up
+/// with one in the old stream; every token from there on is reused, shifted
+/// by the change in length. Tokens that don't move keep their `Arc`s.
+pub fn relex(source: &str, old_tokens: &[Token], edit: TextEdit) -> Vec<Token> {
+    let touched = old_tokens.partition_point(|t| t.span.end < edit.span.start);
+    let first = old_tokens[..touched]
+        .iter()
+        .rposition(|t| t.kind == SyntaxKind::NewLine)
+        .map_or(0, |i| i + 1);
+    let delta = edit.text.len() as isize - edit.span.len() as isize;
+    let inserted_end = edit.span.start + edit.text.len();
+
+    let mut tokens = old_tokens[..first].to_vec();
+    let mut cursor = Cursor::new(source);
+    cursor.advance(first.checked_sub(1).map_or(0, |i| old_tokens[i].span.end));
     let punct = punctuation_tokenizers();
+    let quote = Lexer::default().string_quote;
+    let mut old = first;
 
-    while let Some(&ch) = chars.peek() {
-        if let Some(&lex_fn) = punct.get(&ch) {
-            if let Some(tok) = lex_fn(&mut chars) {
-                tokens.push(Token::new(tok));
-                continue;
+    while cursor.peek().is_some() {
+        let offset = cursor.offset();
+        if offset >= inserted_end {
+            // The rest of the source is unchanged from here, so once the
+            // old stream has a token starting at the same place, so will
+            // every token after it.
+            let old_offset = offset.wrapping_sub_signed(delta);
+            old += old_tokens[old..].partition_point(|t| t.span.start < old_offset);
+            if old_tokens.get(old).is_some_and(|t| t.span.start == old_offset) {
+                tokens.extend(old_tokens[old..].iter().map(|t| shift(t, delta)));
+                return tokens;
             }
         }
+        tokens.push(lex_one(&mut cursor, &punct, quote).to_token(source));
+    }
+    tokens
+}
 
-        if let Some(tok) = lex_whitespace(&mut chars) {
-            tokens.push(Token::new(tok));
-            continue;
+fn shift(token: &Token, delta: isize) -> Token {
+    if delta == 0 {
+        return Token::clone(token);
+    }
+    Token::new(TokenData {
+        kind: token.kind,
+        text: token.text.clone(),
+        span: Span::new(
+            token.span.start.wrapping_add_signed(delta),
+            token.span.end.wrapping_add_signed(delta),
+        ),
+        symbol: token.symbol,
+        raw: token.raw.clone(),
+    })
+}
+
+/// The last token ending at or before `offset`, i.e. the token a cursor at
+/// `offset` sits just after. An offset inside a token yields the token
+/// before it; an offset at the very start yields `None`.
+pub fn token_before_offset(tokens: &[Token], offset: usize) -> Option<&Token> {
+    let idx = tokens.partition_point(|t| t.span.end <= offset);
+    idx.checked_sub(1).map(|i| &tokens[i])
+}
+
+/// An order-sensitive FNV-1a hash over the kind and text of every token.
+/// Spans are ignored, and the hash is stable across runs and platforms, so
+/// it can key a compilation cache.
+pub fn token_stream_hash(tokens: &[Token]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(PRIME);
         }
+    };
+    for tok in tokens {
+        write(&[tok.kind as u8]);
+        // Length prefix keeps `"ab" "c"` distinct from `"a" "bc"`.
+        write(&(tok.text.len() as u64).to_le_bytes());
+        write(tok.text.as_bytes());
+    }
+    hash
+}
 
-        if let Some(tok) = lex_ident_or_keyword(&mut chars) {
-            tokens.push(Token::new(tok));
-            continue;
+/// Kinds that cannot end a statement, so a line break after them continues
+/// the current logical line.
+fn continues_line(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::Equal
+            | SyntaxKind::Colon
+            | SyntaxKind::DoubleColon
+            | SyntaxKind::EqualEqual
+            | SyntaxKind::EqualLess
+            | SyntaxKind::FatArrow
+            | SyntaxKind::ColonEqual
+    )
+}
+
+fn indent_marker(kind: SyntaxKind, offset: usize) -> RawToken {
+    RawToken {
+        kind,
+        span: Span::new(offset, offset),
+    }
+}
+
+/// Inserts zero-width `Indent`/`Dedent` tokens before the first token of
+/// each measured line, closing any open blocks at the end of input.
+fn insert_indentation(tokens: Vec<RawToken>, mode: IndentMode, source: &str) -> Vec<RawToken> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut levels = vec![0usize];
+    let mut last_significant: Option<SyntaxKind> = None;
+    let mut at_line_start = true;
+    let mut width = 0;
+
+    for tok in tokens {
+        if at_line_start {
+            if tok.kind == SyntaxKind::Whitespace {
+                width = tok.text(source).chars().count();
+                out.push(tok);
+                continue;
+            }
+
+            let blank = tok.kind == SyntaxKind::NewLine;
+            let continuation = last_significant.is_some_and(continues_line);
+            let measured = match mode {
+                IndentMode::Logical => !blank && !continuation,
+                _ => true,
+            };
+            if measured {
+                if width > *levels.last().unwrap_or(&0) {
+                    levels.push(width);
+                    out.push(indent_marker(SyntaxKind::Indent, tok.span.start));
+                }
+                while width < *levels.last().unwrap_or(&0) {
+                    levels.pop();
+                    out.push(indent_marker(SyntaxKind::Dedent, tok.span.start));
+                }
+            }
+            at_line_start = false;
         }
 
-        if let Some(tok) = lex_string_literal(&mut chars) {
-            tokens.push(Token::new(tok));
-            continue;
+        match tok.kind {
+            SyntaxKind::NewLine => {
+                at_line_start = true;
+                width = 0;
+            }
+            SyntaxKind::Whitespace => {}
+            kind => last_significant = Some(kind),
         }
+        out.push(tok);
+    }
 
-        // fallback: unknown character
-        chars.next(); // consume one char
-        tokens.push(Token::new(TokenData {
-            kind: SyntaxKind::Error,
-            text: ch.to_string(),
-        }));
+    for _ in 1..levels.len() {
+        out.push(indent_marker(SyntaxKind::Dedent, source.len()));
     }
+    out
+}
 
-    tokens
+/// The indentation convention a file appears to follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    /// Spaces, in steps of the given width. A file with no indented lines
+    /// reports `Spaces(0)`.
+    Spaces(usize),
+    /// Neither tabs nor spaces are a clear majority, or most lines mix both.
+    Mixed,
+}
+
+/// Infers the indentation style of `source` by a majority vote over the
+/// leading whitespace of every non-blank line.
+pub fn detect_indent_style(source: &str) -> IndentStyle {
+    let tokens = table_lex(source);
+    let (mut tabs, mut mixed) = (0, 0);
+    let mut space_widths = Vec::new();
+
+    for (i, tok) in tokens.iter().enumerate() {
+        let line_start = i == 0 || tokens[i - 1].kind == SyntaxKind::NewLine;
+        let blank = tokens.get(i + 1).is_none_or(|t| t.kind == SyntaxKind::NewLine);
+        if !line_start || blank || tok.kind != SyntaxKind::Whitespace {
+            continue;
+        }
+        let text = tok.text.trim_end_matches('\r');
+        if text.chars().all(|c| c == '\t') {
+            tabs += 1;
+        } else if text.chars().all(|c| c == ' ') {
+            space_widths.push(text.len());
+        } else {
+            mixed += 1;
+        }
+    }
+
+    let spaces = space_widths.len();
+    if tabs > spaces && tabs > mixed {
+        IndentStyle::Tabs
+    } else if spaces > tabs && spaces > mixed {
+        // The indent step is the largest width dividing every indented line.
+        IndentStyle::Spaces(space_widths.into_iter().fold(0, gcd))
+    } else if tabs + spaces + mixed == 0 {
+        IndentStyle::Spaces(0)
+    } else {
+        IndentStyle::Mixed
+    }
 }
 
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
 
 /*********************************************************/
 
+// The operator trie is an alternative to `punctuation_tokenizers` that the
+// lexer does not use yet.
+#[allow(dead_code)]
 #[derive(Debug)]
 struct TrieNode {
     kind: Option<SyntaxKind>,
     children: HashMap<char, TrieNode>,
 }
 
+#[allow(dead_code)]
 impl TrieNode {
     fn new() -> Self {
         TrieNode { kind: None, children: HashMap::new() }
@@ -199,7 +1302,7 @@ impl TrieNode {
     }
 }
 
-
+#[allow(dead_code)]
 fn build_operator_trie() -> TrieNode {
     let mut root = TrieNode::new();
     root.insert("=", SyntaxKind::Equal);
@@ -211,39 +1314,47 @@ fn build_operator_trie() -> TrieNode {
     root.insert("::", SyntaxKind::DoubleColon);
     root.insert(";", SyntaxKind::Semicolon);
     root.insert("\n", SyntaxKind::NewLine);
+    root.insert(",", SyntaxKind::Comma);
+    root.insert("(", SyntaxKind::LParen);
+    root.insert(")", SyntaxKind::RParen);
+    root.insert("{", SyntaxKind::LBrace);
+    root.insert("}", SyntaxKind::RBrace);
+    root.insert("[", SyntaxKind::LBracket);
+    root.insert("]", SyntaxKind::RBracket);
+    root.insert(".", SyntaxKind::Dot);
     // Add more as needed
     root
 }
 
 /// # Example
-/// ```
+/// ```ignore
 /// let operator_trie = build_operator_trie();
 /// 
-/// while let Some(&ch) = chars.peek() {
-///     if let Some(tok) = lex_operator(&mut chars, &operator_trie) {
+/// while let Some(ch) = cursor.peek() {
+///     if let Some(tok) = lex_operator(&mut cursor, &operator_trie) {
 ///         tokens.push(Token::new(tok));
 ///         continue;
 ///     }
 /// 
 ///     // fallback for identifier, number, etc.
 /// }
-/// ``` 
-fn lex_operator(chars: &mut Peekable<Chars>, trie: &TrieNode) -> Option<TokenData> {
+/// ```
+#[allow(dead_code)]
+fn lex_operator(cursor: &mut Cursor, trie: &TrieNode) -> Option<TokenData> {
+    let start = cursor.offset();
     let mut node = trie;
     let mut matched = None;
-    let mut matched_text = String::new();
     let mut temp_buffer = String::new();
 
-    let mut iter = chars.clone();
+    let mut iter = cursor.clone();
 
-    while let Some(&ch) = iter.peek() {
+    while let Some(ch) = iter.peek() {
         if let Some(next_node) = node.children.get(&ch) {
             temp_buffer.push(ch);
             iter.next();
             node = next_node;
             if let Some(kind) = node.kind {
-                matched = Some((kind, temp_buffer.clone()));
-                matched_text = temp_buffer.clone();
+                matched = Some((kind, temp_buffer.clone(), iter.clone()));
             }
         } else {
             break;
@@ -251,35 +1362,767 @@ fn lex_operator(chars: &mut Peekable<Chars>, trie: &TrieNode) -> Option<TokenDat
     }
 
     // Actually consume the characters now
-    for _ in 0..matched_text.len() {
-        chars.next();
+    let (kind, text, rest) = matched?;
+    *cursor = rest;
+
+    Some(TokenData {
+        kind,
+        text: text.into(),
+        span: cursor.span_from(start),
+        symbol: None,
+        raw: None,
+    })
+}
+
+/// The operator tokens of `source`, found with [`lex_operator`] alone; any
+/// other character is skipped. Only the benchmarks use it, to time the
+/// trie on its own.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub fn lex_operators(source: &str) -> Vec<TokenData> {
+    static TRIE: std::sync::OnceLock<TrieNode> = std::sync::OnceLock::new();
+    let trie = TRIE.get_or_init(build_operator_trie);
+    let mut cursor = Cursor::new(source);
+    let mut tokens = Vec::new();
+    while cursor.peek().is_some() {
+        match lex_operator(&mut cursor, trie) {
+            Some(tok) => tokens.push(tok),
+            None => {
+                cursor.next();
+            }
+        }
     }
+    tokens
+}
 
-    matched.map(|(kind, text)| TokenData { kind, text })
+
+fn take_while<'a, F: Fn(char) -> bool>(cursor: &mut Cursor<'a>, pred: F) -> &'a str {
+    let start = cursor.offset();
+    while cursor.peek().is_some_and(&pred) {
+        cursor.next();
+    }
+    &cursor.source[start..cursor.offset()]
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn take_while<F: Fn(char) -> bool>(chars: &mut Peekable<Chars>, pred: F) -> String {
-    let mut result = String::new();
-    while let Some(&c) = chars.peek() {
-        if pred(c) {
-            chars.next();
-            result.push(c);
-        } else {
-            break;
+    fn count(tokens: &[Token], kind: SyntaxKind) -> usize {
+        tokens.iter().filter(|t| t.kind == kind).count()
+    }
+
+    fn lex_with(mode: IndentMode, source: &str) -> Vec<Token> {
+        Lexer {
+            indent_mode: mode,
+            ..Lexer::default()
         }
+        .lex(source)
     }
-    result
-}
 
-fn lex_whitespace(chars: &mut Peekable<Chars>) -> Option<TokenData> {
-    let text = take_while(chars, |c| c.is_whitespace() && c != '\n');
-    if text.is_empty() {
-        None
-    } else {
-        Some(TokenData {
-            kind: SyntaxKind::Whitespace,
-            text,
-        })
+    #[test]
+    fn delimiters_lex_to_their_kinds() {
+        let kinds: Vec<_> = table_lex("([{}])").iter().map(|t| t.kind).collect();
+        use SyntaxKind::*;
+        assert_eq!(kinds, [LParen, LBracket, LBrace, RBrace, RBracket, RParen]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn balanced_delimiters() {
+        assert_eq!(check_balanced(&table_lex("")), Ok(()));
+        assert_eq!(check_balanced(&table_lex("let xs = [(a), {b: [c]}];")), Ok(()));
+    }
+
+    #[test]
+    fn unbalanced_delimiters() {
+        let err = check_balanced(&table_lex("a)")).unwrap_err();
+        assert_eq!(
+            err,
+            UnbalancedError::Unmatched {
+                text: ")",
+                span: Span::new(1, 2)
+            }
+        );
+        assert_eq!(err.to_string(), "unmatched closing `)`");
+
+        // The innermost opener is reported.
+        let err = check_balanced(&table_lex("{ [ ]  (")).unwrap_err();
+        assert_eq!(err.to_string(), "unclosed `(`");
+        assert_eq!(err.offset(), 7);
+    }
+
+    #[test]
+    fn mismatched_delimiters() {
+        let err = check_balanced(&table_lex("( ]")).unwrap_err();
+        assert_eq!(
+            err,
+            UnbalancedError::Mismatched {
+                open: "(",
+                close: "]",
+                span: Span::new(2, 3)
+            }
+        );
+        assert_eq!(err.offset(), 2);
+        assert_eq!(err.to_string(), "`(` closed by `]`");
+    }
+
+    #[test]
+    fn doc_comments_need_exactly_three_slashes() {
+        let kinds = |source: &str| -> Vec<_> { table_lex(source).iter().map(|t| t.kind).collect() };
+        assert_eq!(kinds("// plain"), [SyntaxKind::LineComment]);
+        assert_eq!(kinds("/// docs"), [SyntaxKind::DocComment]);
+        assert_eq!(kinds("///"), [SyntaxKind::DocComment]);
+        assert_eq!(kinds("//// ruler"), [SyntaxKind::LineComment]);
+
+        let tokens = table_lex("/// x\nlet");
+        assert_eq!(tokens[0].text, "/// x");
+        assert_eq!(tokens[1].kind, SyntaxKind::NewLine);
+    }
+
+    #[test]
+    fn lex_into_reuses_the_buffer() {
+        let mut tokens = Vec::new();
+        lex_into("let a = \"x\"; let b = 2;", &mut tokens);
+        let capacity = tokens.capacity();
+        let buffer = tokens.as_ptr();
+
+        lex_into("let c;", &mut tokens);
+        assert_eq!(tokens, table_lex("let c;"));
+        assert_eq!(tokens.capacity(), capacity);
+        assert_eq!(tokens.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn spanned_iter_matches_table_lex() {
+        let source = "let é: string = \"😀 x\"; // ü\n@";
+        let pairs: Vec<_> = lex_spanned_iter(source).collect();
+        let expected: Vec<_> = table_lex(source).into_iter().map(|t| (t.clone(), t.span)).collect();
+        assert_eq!(pairs, expected);
+
+        let (tok, span) = lex_spanned_iter(source).nth(2).unwrap();
+        assert_eq!((&*tok.text, &source[span.start..span.end]), ("é", "é"));
+        assert_eq!(lex_spanned_iter("").count(), 0);
+    }
+
+    #[test]
+    fn commas_are_punctuation() {
+        let tokens = table_lex("a, b,c");
+        assert_eq!(count(&tokens, SyntaxKind::Comma), 2);
+        assert_eq!(count(&tokens, SyntaxKind::Error), 0);
+        assert_eq!(table_lex(",")[0].kind, SyntaxKind::Comma);
+
+        let trie = build_operator_trie();
+        let tok = lex_operator(&mut Cursor::new(","), &trie).unwrap();
+        assert_eq!((tok.kind, tok.span), (SyntaxKind::Comma, Span::new(0, 1)));
+    }
+
+    #[cfg(feature = "bench")]
+    #[test]
+    fn lex_operators_finds_only_punctuation() {
+        let kinds: Vec<_> = lex_operators("a::b := c;").iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [SyntaxKind::DoubleColon, SyntaxKind::ColonEqual, SyntaxKind::Semicolon]
+        );
+    }
+
+    #[test]
+    fn indentation_is_off_by_default() {
+        let tokens = table_lex("a\n    b\n");
+        assert_eq!(count(&tokens, SyntaxKind::Indent), 0);
+        assert_eq!(count(&tokens, SyntaxKind::Dedent), 0);
+    }
+
+    #[test]
+    fn blank_lines_do_not_close_blocks_in_logical_mode() {
+        let source = "a\n    b\n\n    c\n  \n    d\ne";
+        let tokens = lex_with(IndentMode::Logical, source);
+        assert_eq!(count(&tokens, SyntaxKind::Indent), 1);
+        assert_eq!(count(&tokens, SyntaxKind::Dedent), 1);
+
+        let dedent = tokens
+            .iter()
+            .position(|t| t.kind == SyntaxKind::Dedent)
+            .unwrap();
+        assert_eq!(tokens[dedent + 1].text, "e");
+
+        // Physical mode treats each blank line as a return to column zero.
+        let tokens = lex_with(IndentMode::Physical, source);
+        assert_eq!(count(&tokens, SyntaxKind::Indent), 3);
+        assert_eq!(count(&tokens, SyntaxKind::Dedent), 3);
+    }
+
+    #[test]
+    fn continuation_lines_do_not_open_blocks_in_logical_mode() {
+        let source = "let x: string =\n        \"v\";\nlet y: string = \"w\";";
+        let tokens = lex_with(IndentMode::Logical, source);
+        assert_eq!(count(&tokens, SyntaxKind::Indent), 0);
+        assert_eq!(count(&tokens, SyntaxKind::Dedent), 0);
+    }
+
+    #[test]
+    fn open_blocks_are_closed_at_end_of_input() {
+        let tokens = lex_with(IndentMode::Logical, "a\n  b\n    c");
+        assert_eq!(count(&tokens, SyntaxKind::Indent), 2);
+        assert_eq!(count(&tokens, SyntaxKind::Dedent), 2);
+        assert_eq!(tokens.last().unwrap().kind, SyntaxKind::Dedent);
+    }
+
+    #[test]
+    fn spans_are_byte_ranges_into_the_source() {
+        let source = "let é = \"ü\";";
+        for tok in table_lex(source) {
+            let slice = &source[tok.span.start..tok.span.end];
+            match tok.kind {
+                SyntaxKind::StringLiteral => assert_eq!(slice, "\"ü\""),
+                _ => assert_eq!(slice, tok.text),
+            }
+        }
+    }
+
+    #[test]
+    fn lexes_integer_and_float_literals() {
+        let tokens: Vec<_> = table_lex("42 4.2 7.")
+            .into_iter()
+            .filter(|t| t.kind != SyntaxKind::Whitespace)
+            .map(|t| (t.kind, t.text.to_string()))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (SyntaxKind::IntLiteral, "42".to_string()),
+                (SyntaxKind::FloatLiteral, "4.2".to_string()),
+                (SyntaxKind::IntLiteral, "7".to_string()),
+                (SyntaxKind::Dot, ".".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn digit_separators() {
+        let lexed = |source| {
+            table_lex(source)
+                .iter()
+                .map(|t| (t.kind, t.text.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lexed("1_000"), [(SyntaxKind::IntLiteral, "1_000".to_string())]);
+        assert_eq!(lexed("1_0.2_5"), [(SyntaxKind::FloatLiteral, "1_0.2_5".to_string())]);
+        assert_eq!(lexed("1__0"), [(SyntaxKind::Error, "1__0".to_string())]);
+        assert_eq!(lexed("1_"), [(SyntaxKind::Error, "1_".to_string())]);
+        assert_eq!(
+            lexed("_5"),
+            [
+                (SyntaxKind::Unknown, "_".to_string()),
+                (SyntaxKind::IntLiteral, "5".to_string()),
+            ]
+        );
+        assert!(matches!(
+            &lex_errors(&table_lex("1__0"))[..],
+            [LexError::InvalidNumber { text, .. }] if text == "1__0"
+        ));
+    }
+
+    #[test]
+    fn lexes_prefixed_integer_literals() {
+        for source in ["0x1F", "0o17", "0b1010", "0xff_ff"] {
+            let tokens = table_lex(source);
+            assert_eq!(tokens.len(), 1, "{source}");
+            assert_eq!(tokens[0].kind, SyntaxKind::IntLiteral, "{source}");
+            assert_eq!(tokens[0].text, source);
+        }
+
+        let tokens = table_lex("0b12");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, SyntaxKind::Error);
+        let errors = lex_errors(&tokens);
+        assert_eq!(
+            errors,
+            [LexError::InvalidDigit {
+                digit: '2',
+                radix: 2,
+                span: Span::new(0, 4),
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "invalid digit `2` in a base-2 literal");
+        assert!(matches!(
+            &lex_errors(&table_lex("0x"))[..],
+            [LexError::InvalidNumber { .. }]
+        ));
+    }
+
+    #[test]
+    fn lex_interned_attaches_symbols_to_identifiers() {
+        let mut interner = StringInterner::new();
+        let tokens = Lexer::new().lex_interned("let a = b; let b = a;", &mut interner);
+        let symbols: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.kind == SyntaxKind::Ident)
+            .map(|t| t.symbol.unwrap())
+            .collect();
+        assert_eq!(symbols[0], symbols[3]);
+        assert_eq!(symbols[1], symbols[2]);
+        assert_ne!(symbols[0], symbols[1]);
+        assert_eq!(interner.resolve(symbols[1]), "b");
+        assert!(tokens.iter().all(|t| (t.kind == SyntaxKind::Ident) == t.symbol.is_some()));
+    }
+
+    #[test]
+    fn tokens_to_string_reproduces_the_source() {
+        for source in [
+            "let a: string = \"x\"; // c\n\tlet b = 0x1F,c=1.5 /* d */\n",
+            "\"\"\"multi\nline\"\"\" @ \"open",
+        ] {
+            assert_eq!(tokens_to_string(&table_lex(source)), source);
+        }
+    }
+
+    #[test]
+    fn lex_bytes_validates_utf8() {
+        let tokens = lex_bytes("let é = 1;".as_bytes()).unwrap();
+        assert_eq!(tokens, table_lex("let é = 1;"));
+
+        let err = lex_bytes(b"let \xff = 1;").unwrap_err();
+        assert_eq!(err.valid_up_to(), 4);
+    }
+
+    #[test]
+    fn lex_significant_skips_trivia() {
+        let source = "let a = 1; // one\n/* two */ let b = 2;\n";
+        let tokens = lex_significant(source);
+        assert!(tokens.iter().all(|t| !t.kind.is_trivia()));
+        let expected: Vec<_> = table_lex(source)
+            .into_iter()
+            .filter(|t| !t.kind.is_trivia())
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn token_before_offset_finds_the_preceding_token() {
+        let tokens = table_lex("let x: string");
+        let before = |offset| token_before_offset(&tokens, offset).map(|t| t.kind);
+
+        assert_eq!(before(0), None);
+        assert_eq!(before(2), None); // inside `let`
+        assert_eq!(before(3), Some(SyntaxKind::Let));
+        assert_eq!(before(5), Some(SyntaxKind::Ident));
+        assert_eq!(before(6), Some(SyntaxKind::Colon));
+        assert_eq!(before(9), Some(SyntaxKind::Whitespace)); // inside `string`
+        assert_eq!(before(13), Some(SyntaxKind::Type));
+        assert_eq!(before(100), Some(SyntaxKind::Type));
+    }
+
+    #[test]
+    fn fixed_punctuation_shares_its_text() {
+        let tokens = table_lex("a;b;");
+        let (first, second) = (&tokens[1], &tokens[3]);
+        assert_eq!(first.kind, SyntaxKind::Semicolon);
+        assert!(matches!(first.text, Cow::Borrowed(_)));
+        assert!(std::ptr::eq(first.text.as_ptr(), second.text.as_ptr()));
+    }
+
+    #[test]
+    fn raw_tokens_borrow_from_the_source() {
+        let source = "let alpha_beta: string = \"gamma\";\n".repeat(1000);
+        let lexer = Lexer::new();
+        let raw = lexer.lex_raw(&source);
+        assert_eq!(raw[2].text(&source), "alpha_beta");
+        assert!(source.as_bytes().as_ptr_range().contains(&raw[2].text(&source).as_ptr()));
+
+        let owned: Vec<_> = raw.iter().map(|t| t.to_token(&source)).collect();
+        assert_eq!(owned, table_lex(&source));
+        assert_eq!(owned[9].text, "gamma");
+    }
+
+    #[test]
+    fn token_ext_accessors() {
+        let tokens = table_lex("let x;");
+        assert_eq!(tokens[0].kind(), SyntaxKind::Let);
+        assert_eq!(tokens[2].text(), "x");
+        let idents: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.kind() == SyntaxKind::Ident)
+            .map(TokenExt::text)
+            .collect();
+        assert_eq!(idents, ["x"]);
+    }
+
+    #[test]
+    fn eof_mode_appends_an_empty_token_at_the_end() {
+        let source = "let x = 1;\n";
+        let lexer = Lexer {
+            eof: true,
+            ..Lexer::default()
+        };
+        let tokens = lexer.lex(source);
+        let last = tokens.last().unwrap();
+        assert_eq!(last.kind, SyntaxKind::Eof);
+        assert_eq!(last.text, "");
+        assert_eq!(last.span, Span::new(source.len(), source.len()));
+        assert_eq!(tokens[..tokens.len() - 1], table_lex(source)[..]);
+
+        assert_eq!(lexer.lex("")[0].span, Span::new(0, 0));
+        assert_eq!(count(&Lexer::default().lex(source), SyntaxKind::Eof), 0);
+    }
+
+    #[test]
+    fn keywords_can_match_in_any_case() {
+        let lexer = Lexer {
+            case_insensitive_keywords: true,
+            ..Lexer::default()
+        };
+        let tokens = lexer.lex("LET x: String = True");
+        assert_eq!((tokens[0].kind, &*tokens[0].text), (SyntaxKind::Let, "LET"));
+        assert_eq!(tokens[2].kind, SyntaxKind::Ident);
+        assert_eq!(tokens[5].kind, SyntaxKind::Type);
+        assert_eq!(tokens[9].kind, SyntaxKind::BoolLiteral);
+
+        assert_eq!(Lexer::default().lex("LET")[0].kind, SyntaxKind::Ident);
+    }
+
+    #[test]
+    fn configurable_string_quote() {
+        let lexer = Lexer {
+            string_quote: '\'',
+            ..Lexer::default()
+        };
+        let tokens = lexer.lex(r#"'hi' 'it\'s' "no""#);
+        assert_eq!(tokens[0].kind, SyntaxKind::StringLiteral);
+        assert_eq!(tokens[0].text, "hi");
+        assert_eq!(tokens[0].span, Span::new(0, 4));
+        assert_eq!(tokens[2].kind, SyntaxKind::StringLiteral);
+        assert_eq!(tokens[2].text, r"it\'s");
+        assert_eq!(tokens[4].kind, SyntaxKind::Unknown);
+
+        let tokens = table_lex(r#""say \"hi\"""#);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].text, r#"say \"hi\""#);
+    }
+
+    #[test]
+    fn custom_string_quote_round_trips_and_reports_errors() {
+        let lexer = Lexer {
+            string_quote: '\'',
+            ..Lexer::default()
+        };
+        let source = "let a = 'hi', b = '''x'y''';";
+        let tokens = lexer.lex(source);
+        assert_eq!(tokens_to_string(&tokens), source);
+        let root = crate::parse_tokens_to_cst(&tokens);
+        assert_eq!(crate::cst_to_source(&root), source);
+
+        let tokens = lexer.lex("let a = 'abc");
+        assert_eq!(
+            lexer.lex_errors(&tokens),
+            [LexError::UnterminatedString {
+                span: Span::new(8, 12)
+            }]
+        );
+        let tokens = lexer.lex(r"'\u{zz}'");
+        assert!(matches!(
+            lexer.lex_errors(&tokens)[..],
+            [LexError::InvalidEscape { .. }]
+        ));
+    }
+
+    #[test]
+    fn lexes_triple_quoted_strings() {
+        let source = "\"\"\"one \"quoted\"\ntwo\"\"\";";
+        let tokens = table_lex(source);
+        assert_eq!(tokens[0].kind, SyntaxKind::StringLiteral);
+        assert_eq!(tokens[0].text, "one \"quoted\"\ntwo");
+        assert_eq!(tokens[0].span, Span::new(0, source.len() - 1));
+        assert_eq!(tokens[1].kind, SyntaxKind::Semicolon);
+
+        let tokens = table_lex("\"\"\"open\n\"");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, SyntaxKind::Error);
+        assert_eq!(tokens[0].text, "\"\"\"open\n\"");
+        assert!(matches!(lex_errors(&tokens)[..], [LexError::UnterminatedString { .. }]));
+    }
+
+    #[test]
+    fn tokens_compare_to_kinds() {
+        let tokens = table_lex("a: b;");
+        if tokens[1] != SyntaxKind::Colon {
+            panic!("expected a colon, got {}", tokens[1]);
+        }
+        assert!(*tokens[1] == SyntaxKind::Colon);
+        let idents = tokens.iter().filter(|t| **t == SyntaxKind::Ident).count();
+        assert_eq!(idents, 2);
+    }
+
+    /// Applies `edit` to `old`, relexes, and checks the result against a
+    /// full lex of the new text.
+    fn relex_matches(old: &str, start: usize, end: usize, text: &str) -> (Vec<Token>, Vec<Token>) {
+        let old_tokens = table_lex(old);
+        let new = format!("{}{text}{}", &old[..start], &old[end..]);
+        let edit = TextEdit {
+            span: Span::new(start, end),
+            text: text.to_string(),
+        };
+        let tokens = relex(&new, &old_tokens, edit);
+        assert_eq!(tokens, table_lex(&new), "{old:?} -> {new:?}");
+        (old_tokens, tokens)
+    }
+
+    #[test]
+    fn relex_reuses_tokens_outside_the_edit() {
+        let old = "let a = 1;\nlet b = 2;\nlet c = 3;\n";
+        let (old_tokens, tokens) = relex_matches(old, 19, 20, "7");
+        let reused = |i: usize| Arc::ptr_eq(&old_tokens[i], &tokens[i]);
+        // The first line is untouched, the second is relexed up to the
+        // edited `2`, and everything after it is reused.
+        assert!((0..9).all(reused));
+        assert!(!reused(15));
+        assert!((16..tokens.len()).all(reused));
+    }
+
+    #[test]
+    fn relex_handles_length_changes() {
+        let old = "let a = 1;\nlet b = \"x\";\n// done\n";
+        relex_matches(old, 15, 16, "bee");
+        relex_matches(old, 4, 5, "");
+        relex_matches(old, 0, 0, "/* ");
+        relex_matches(old, old.len(), old.len(), "let");
+        relex_matches(old, 19, 20, "");
+    }
+
+    #[test]
+    fn lexes_line_and_block_comments() {
+        let tokens: Vec<_> = table_lex("a // note\n/* x\ny */ /* open")
+            .into_iter()
+            .map(|t| (t.kind, t.text.to_string()))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                (SyntaxKind::Ident, "a".to_string()),
+                (SyntaxKind::Whitespace, " ".to_string()),
+                (SyntaxKind::LineComment, "// note".to_string()),
+                (SyntaxKind::NewLine, "\n".to_string()),
+                (SyntaxKind::BlockComment, "/* x\ny */".to_string()),
+                (SyntaxKind::Whitespace, " ".to_string()),
+                (SyntaxKind::Error, "/* open".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn token_stream_hash_is_stable_and_order_sensitive() {
+        let hash = |src| token_stream_hash(&table_lex(src));
+        assert_eq!(hash("let x = \"a\";"), hash("let x = \"a\";"));
+        assert_ne!(hash("let x = \"a\";"), hash("let x = \"b\";"));
+        assert_ne!(hash("a b"), hash("b a"));
+        // Only kinds and texts count, not where the tokens sit.
+        let mut shifted = table_lex("let x");
+        shifted[0] = Arc::new(TokenData {
+            span: Span::new(10, 13),
+            ..(*shifted[0]).clone()
+        });
+        assert_eq!(token_stream_hash(&shifted), hash("let x"));
+    }
+
+    #[test]
+    fn detects_tab_indentation() {
+        assert_eq!(detect_indent_style("a\n\tb\n\t\tc\n"), IndentStyle::Tabs);
+    }
+
+    #[test]
+    fn detects_four_space_indentation() {
+        let source = "a\n    b\n        c\n\n    d\n";
+        assert_eq!(detect_indent_style(source), IndentStyle::Spaces(4));
+        assert_eq!(detect_indent_style("a\nb\n"), IndentStyle::Spaces(0));
+    }
+
+    #[test]
+    fn detects_mixed_indentation() {
+        assert_eq!(detect_indent_style("a\n\tb\n    c\n"), IndentStyle::Mixed);
+        assert_eq!(detect_indent_style("a\n \tb\n"), IndentStyle::Mixed);
+        // A single stray line loses the vote.
+        assert_eq!(detect_indent_style("a\n\tb\n\tc\n  d\n"), IndentStyle::Tabs);
+    }
+
+    #[test]
+    fn unicode_escapes_decode() {
+        let tokens = table_lex(r#""\u{41}\u{1F600}\n""#);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, SyntaxKind::StringLiteral);
+        // The token keeps the escapes as written.
+        assert_eq!(tokens[0].text, r"\u{41}\u{1F600}\n");
+        assert_eq!(unescape_unicode(&tokens[0].text).unwrap(), "A😀\\n");
+        assert_eq!(unescape_unicode(r"\\u{41}").unwrap(), r"\\u{41}");
+    }
+
+    #[test]
+    fn max_tokens_truncates_with_a_limit_marker() {
+        let lexer = Lexer {
+            max_tokens: Some(3),
+            ..Lexer::default()
+        };
+        let source = "let x = 1;";
+        let tokens = lexer.lex(source);
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SyntaxKind::Let,
+                SyntaxKind::Whitespace,
+                SyntaxKind::Ident,
+                SyntaxKind::TokenLimit,
+            ]
+        );
+        assert_eq!(tokens[3].span, Span::new(5, 5));
+        assert_eq!(
+            lex_errors(&tokens),
+            [LexError::TokenLimit {
+                span: Span::new(5, 5)
+            }]
+        );
+
+        // Input that fits is untouched, and there is no limit by default.
+        let lexer = Lexer {
+            max_tokens: Some(8),
+            ..Lexer::default()
+        };
+        assert_eq!(lexer.lex(source), table_lex(source));
+        assert_eq!(Lexer::default().max_tokens, None);
+
+        // Only the marker kind means the limit, not an empty `Error`.
+        let empty = Token::new(TokenData {
+            kind: SyntaxKind::Error,
+            text: "".into(),
+            span: Span::new(0, 0),
+            symbol: None,
+            raw: None,
+        });
+        assert!(!matches!(lex_errors(&[empty])[..], [LexError::TokenLimit { .. }]));
+    }
+
+    #[test]
+    fn unescaped_strings_keep_the_raw_literal() {
+        let lexer = Lexer {
+            unescape_strings: true,
+            ..Lexer::default()
+        };
+        let source = r#"let s = "a\nb", t = "\u{41}\\\"";"#;
+        let tokens = lexer.lex(source);
+        let strings: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.kind == SyntaxKind::StringLiteral)
+            .collect();
+        assert_eq!(strings[0].text, "a\nb");
+        assert_eq!(strings[0].raw.as_deref(), Some(r#""a\nb""#));
+        assert_eq!(strings[1].text, "A\\\"");
+        assert_eq!(strings[1].raw.as_deref(), Some(r#""\u{41}\\\"""#));
+        assert!(tokens.iter().all(|t| t.raw.is_none() || t.kind == SyntaxKind::StringLiteral));
+        assert_eq!(tokens_to_string(&tokens), source);
+
+        // Off by default: the text keeps its escapes.
+        let tokens = table_lex(source);
+        assert_eq!(tokens[6].raw.as_deref(), Some(r#""a\nb""#));
+        assert_eq!(tokens[6].text, r"a\nb");
+        assert_eq!(unescape(&tokens[6].text).unwrap(), "a\nb");
+        assert_eq!(unescape(r"\q\t").unwrap(), "\\q\t");
+    }
+
+    #[test]
+    fn invalid_unicode_escapes_are_errors() {
+        let source = r#"let x = "a\u{110000}";"#;
+        let tokens = table_lex(source);
+        assert_eq!(tokens[6].kind, SyntaxKind::Error);
+        assert_eq!(tokens[6].text, r#""a\u{110000}""#);
+        let errors = lex_errors(&tokens);
+        assert_eq!(
+            errors,
+            [LexError::InvalidEscape {
+                reason: EscapeError::OutOfRange(0x110000),
+                span: Span::new(10, 20),
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "invalid unicode escape: `110000` is not a Unicode scalar value"
+        );
+
+        for (source, reason) in [
+            (r#""\u{1234567}""#, EscapeError::TooLong),
+            (r#""\u{zz}""#, EscapeError::Malformed),
+            (r#""\u41""#, EscapeError::Malformed),
+            (r#""\u{D800}""#, EscapeError::OutOfRange(0xD800)),
+        ] {
+            let errors = lex_errors(&table_lex(source));
+            assert!(
+                matches!(&errors[..], [LexError::InvalidEscape { reason: r, .. }] if *r == reason),
+                "{source}: {errors:?}"
+            );
+        }
+        // Unterminated wins over a bad escape.
+        assert!(matches!(
+            lex_errors(&table_lex(r#""\u{zz}"#))[..],
+            [LexError::UnterminatedString { .. }]
+        ));
+    }
+
+    #[test]
+    fn unrecognized_characters_are_unknown() {
+        let tokens = table_lex("@ \"open");
+        assert_eq!(tokens[0].kind, SyntaxKind::Unknown);
+        assert_eq!(tokens[0].text, "@");
+        assert_eq!(tokens[2].kind, SyntaxKind::Error);
+        assert_eq!(tokens[0].kind.category(), crate::SyntaxCategory::Error);
+    }
+
+    #[test]
+    fn adjacent_errors_merge() {
+        let tokens = merge_adjacent_errors(table_lex("@@@"));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, SyntaxKind::Unknown);
+        assert_eq!(tokens[0].text, "@@@");
+        assert_eq!(tokens[0].span, Span::new(0, 3));
+
+        let tokens = merge_adjacent_errors(table_lex("@ $%0x1G;"));
+        let kinds: Vec<_> = tokens.iter().map(|t| (t.kind, &*t.text)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (SyntaxKind::Unknown, "@"),
+                (SyntaxKind::Whitespace, " "),
+                (SyntaxKind::Error, "$%0x1G"),
+                (SyntaxKind::Semicolon, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_errors_classify_error_tokens() {
+        let errors = lex_errors(&table_lex("let x = @; /* open \"s"));
+        assert_eq!(
+            errors,
+            [
+                LexError::UnexpectedChar {
+                    text: "@".into(),
+                    span: Span::new(8, 9),
+                },
+                LexError::UnterminatedComment {
+                    span: Span::new(11, 21),
+                },
+            ]
+        );
+        let errors = lex_errors(&table_lex("let s = \"abc"));
+        assert_eq!(errors, [LexError::UnterminatedString { span: Span::new(8, 12) }]);
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn lex_error_labels_the_offending_character() {
+        use miette::Diagnostic;
+
+        let source = "let x = @;";
+        let errors = lex_errors(&table_lex(source));
+        let label = errors[0].labels().unwrap().next().unwrap();
+        assert_eq!((label.offset(), label.len()), (source.find('@').unwrap(), 1));
+        assert_eq!(label.label(), Some("not valid here"));
+    }
+}
@@ -1,15 +1,38 @@
+mod ast;
+mod diagnostic;
+mod format;
+mod highlight;
+mod intern;
 mod kind;
 mod lex;
+mod lsp;
 mod node;
 mod semantic;
+mod span;
+mod stream;
+mod value;
 mod old_lexer;
 mod parse;
-mod api;
+#[cfg(feature = "ariadne")]
+mod report;
+pub mod api;
+pub mod prelude;
 
 
+pub use ast::*;
+pub use diagnostic::*;
+pub use format::*;
 pub use old_lexer::*;
 pub use parse::*;
+#[cfg(feature = "ariadne")]
+pub use report::*;
+pub use highlight::*;
+pub use intern::*;
 pub use kind::*;
 pub use lex::*;
+pub use lsp::*;
 pub use node::*;
 pub use semantic::*;
+pub use span::*;
+pub use stream::*;
+pub use value::*;
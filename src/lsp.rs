@@ -0,0 +1,415 @@
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, DocumentSymbol, FoldingRange, FoldingRangeKind, Hover,
+    HoverContents, MarkupContent, MarkupKind, Position, Range, SymbolKind, TextEdit,
+};
+
+use crate::{
+    LexError, Span, SyntaxCategory, SyntaxKind, lex_errors, lower_to_ast, parse_tokens_to_cst,
+    table_lex, token_before_offset,
+};
+
+/// Whether a cursor at byte `offset` sits inside a string literal, i.e.
+/// after its opening quote and before its closing one. A cursor just before
+/// the opening quote or just after the closing quote is outside. An
+/// unterminated string runs to the end of the text.
+pub fn is_inside_string(text: &str, offset: usize) -> bool {
+    table_lex(text).iter().any(|tok| {
+        let span = tok.span;
+        match tok.kind {
+            SyntaxKind::StringLiteral => span.start < offset && offset < span.end,
+            SyntaxKind::Error if text[span.start..].starts_with('"') => {
+                // A literal rejected for a bad escape still has its closing quote.
+                let unterminated = matches!(
+                    lex_errors(std::slice::from_ref(tok))[..],
+                    [LexError::UnterminatedString { .. }]
+                );
+                span.start < offset && (offset < span.end || unterminated && offset == span.end)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Hover text for the token covering byte `offset`, describing its kind.
+/// Trivia and positions between tokens have no hover.
+pub fn hover(text: &str, offset: usize) -> Option<Hover> {
+    let tokens = table_lex(text);
+    let tok = tokens
+        .iter()
+        .find(|t| t.span.contains(offset))?;
+    let description = match tok.kind {
+        SyntaxKind::Let => "keyword `let`: introduces a mutable binding".to_string(),
+        SyntaxKind::Const => "keyword `const`: introduces an immutable binding".to_string(),
+        SyntaxKind::Use => "keyword `use`: imports a module or file".to_string(),
+        SyntaxKind::Ident => format!("identifier `{}`", tok.text),
+        SyntaxKind::Type => format!("type `{}`", tok.text),
+        SyntaxKind::StringLiteral => "string literal".to_string(),
+        SyntaxKind::IntLiteral => "integer literal".to_string(),
+        SyntaxKind::FloatLiteral => "float literal".to_string(),
+        SyntaxKind::BoolLiteral => "boolean literal".to_string(),
+        kind => match kind.category() {
+            SyntaxCategory::Trivia | SyntaxCategory::Layout => return None,
+            SyntaxCategory::Punctuation => format!("`{}`", tok.text),
+            _ => format!("{kind}"),
+        },
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: description,
+        }),
+        range: None,
+    })
+}
+
+/// One symbol per declared binding, for the editor's outline. The range
+/// covers the binding from name to value; the selection range is the name.
+pub fn document_symbols(text: &str) -> Vec<DocumentSymbol> {
+    let root = parse_tokens_to_cst(&table_lex(text));
+    lower_to_ast(&root)
+        .into_iter()
+        .map(|decl| {
+            #[allow(deprecated)] // `deprecated` has no default and must be spelled out.
+            DocumentSymbol {
+                name: decl.name,
+                detail: decl.ty,
+                kind: if decl.mutable {
+                    SymbolKind::VARIABLE
+                } else {
+                    SymbolKind::CONSTANT
+                },
+                tags: None,
+                deprecated: None,
+                range: range(text, decl.span),
+                selection_range: range(text, decl.name_span),
+                children: None,
+            }
+        })
+        .collect()
+}
+
+/// Foldable regions: block comments and statements that span several
+/// lines. A statement's region ends at its last significant token, so a
+/// trailing comment does not extend it.
+pub fn folding_ranges(text: &str) -> Vec<FoldingRange> {
+    let tokens = table_lex(text);
+    let mut ranges = Vec::new();
+    let mut fold = |span: Span, kind: Option<FoldingRangeKind>| {
+        let (start, end) = (position(text, span.start), position(text, span.end));
+        if end.line > start.line {
+            ranges.push(FoldingRange {
+                start_line: start.line,
+                start_character: None,
+                end_line: end.line,
+                end_character: None,
+                kind,
+                collapsed_text: None,
+            });
+        }
+    };
+
+    for node in parse_tokens_to_cst(&tokens).child_nodes() {
+        let tokens = node.tokens();
+        let mut significant = tokens.iter().filter(|t| !t.kind.is_trivia());
+        if let (Some(first), Some(last)) = (significant.next(), significant.next_back()) {
+            fold(
+                first.span.merge(last.span),
+                Some(FoldingRangeKind::Region),
+            );
+        }
+    }
+    for tok in tokens.iter().filter(|t| t.kind == SyntaxKind::BlockComment) {
+        fold(tok.span, Some(FoldingRangeKind::Comment));
+    }
+
+    ranges.sort_by_key(|r| r.start_line);
+    ranges
+}
+
+/// Edits renaming the variable at byte `offset` to `new_name`: every
+/// declaration of it and every value referring to it. `None` unless the
+/// cursor is on, or just after, a variable name.
+pub fn rename(text: &str, offset: usize, new_name: &str) -> Option<Vec<TextEdit>> {
+    let root = parse_tokens_to_cst(&table_lex(text));
+    let names: Vec<_> = root
+        .child_nodes()
+        .into_iter()
+        .filter(|node| node.kind() == SyntaxKind::VarDecl)
+        .flat_map(|node| node.descendant_tokens())
+        .filter(|tok| !tok.kind.is_trivia())
+        // A name after a `.` is a member, not a variable.
+        .scan(SyntaxKind::Eof, |prev, tok| {
+            let member = std::mem::replace(prev, tok.kind) == SyntaxKind::Dot;
+            Some((!member && tok.kind == SyntaxKind::Ident).then(|| tok.clone()))
+        })
+        .flatten()
+        .collect();
+    let target = names
+        .iter()
+        .find(|tok| tok.span.contains(offset) || tok.span.end == offset)?;
+    let edits = names
+        .iter()
+        .filter(|tok| tok.text == target.text)
+        .map(|tok| TextEdit {
+            range: range(text, tok.span),
+            new_text: new_name.to_string(),
+        })
+        .collect();
+    Some(edits)
+}
+
+const STATEMENT_KEYWORDS: &[&str] = &["let", "const", "use"];
+
+/// Completion candidates at byte `offset`: statement keywords at the start
+/// of a statement, and type names after a `:`. The word being typed at the
+/// cursor, if any, is ignored when deciding the context.
+pub fn completions(text: &str, offset: usize) -> Vec<CompletionItem> {
+    if is_inside_string(text, offset) {
+        return Vec::new();
+    }
+    let tokens = table_lex(text);
+    let mut end = offset;
+    let context = loop {
+        match token_before_offset(&tokens, end) {
+            Some(tok) if tok.kind.is_trivia() => end = tok.span.start,
+            // A word ending at the cursor is the one being completed.
+            Some(tok)
+                if tok.span.end == offset
+                    && matches!(
+                        tok.kind.category(),
+                        SyntaxCategory::Keyword | SyntaxCategory::Identifier
+                    ) =>
+            {
+                end = tok.span.start
+            }
+            other => break other.map(|t| t.kind),
+        }
+    };
+
+    let (labels, kind) = match context {
+        None | Some(SyntaxKind::Semicolon) => (STATEMENT_KEYWORDS, CompletionItemKind::KEYWORD),
+        // The names the lexer reads as a `Type`.
+        Some(SyntaxKind::Colon) => (SyntaxKind::Type.spellings(), CompletionItemKind::CLASS),
+        _ => return Vec::new(),
+    };
+    labels
+        .iter()
+        .map(|label| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The LSP position of byte `offset`: zero-based line, and column in UTF-16
+/// code units.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+fn range(text: &str, span: Span) -> Range {
+    Range {
+        start: position(text, span.start),
+        end: position(text, span.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_inside_a_string() {
+        let text = "let x = \"abc\";";
+        let open = text.find('"').unwrap();
+        assert!(is_inside_string(text, open + 1));
+        assert!(is_inside_string(text, open + 3));
+        // Just before the closing quote.
+        assert!(is_inside_string(text, open + 4));
+    }
+
+    #[test]
+    fn cursor_on_the_quotes_is_outside() {
+        let text = "let x = \"abc\";";
+        let open = text.find('"').unwrap();
+        let close = text.rfind('"').unwrap();
+        assert!(!is_inside_string(text, open));
+        assert!(!is_inside_string(text, close + 1));
+        // An empty string still has a position between its quotes.
+        assert!(is_inside_string("\"\"", 1));
+    }
+
+    #[test]
+    fn cursor_outside_any_string() {
+        let text = "let x = \"abc\";";
+        assert!(!is_inside_string(text, 0));
+        assert!(!is_inside_string(text, 4));
+        assert!(!is_inside_string(text, text.len()));
+    }
+
+    #[test]
+    fn cursor_after_a_literal_with_a_bad_escape_is_outside() {
+        for text in ["let x = \"\\q\";", "let x = \"\\u{zz}\";"] {
+            let open = text.find('"').unwrap();
+            let close = text.rfind('"').unwrap();
+            assert!(is_inside_string(text, open + 1), "{text}");
+            assert!(is_inside_string(text, close), "{text}");
+            assert!(!is_inside_string(text, close + 1), "{text}");
+        }
+    }
+
+    #[test]
+    fn unterminated_string_runs_to_the_end() {
+        let text = "let x = \"ab";
+        assert!(is_inside_string(text, text.len()));
+        assert!(!is_inside_string(text, text.find('"').unwrap()));
+    }
+
+    fn hover_text(text: &str, offset: usize) -> Option<String> {
+        match hover(text, offset)?.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            other => panic!("unexpected hover contents: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn hover_over_the_let_keyword() {
+        let text = "let name = \"x\";";
+        let expected = "keyword `let`: introduces a mutable binding";
+        assert_eq!(hover_text(text, 0).as_deref(), Some(expected));
+        assert_eq!(hover_text(text, 2).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn hover_over_an_identifier() {
+        let text = "let name = \"x\";";
+        assert_eq!(hover_text(text, 5).as_deref(), Some("identifier `name`"));
+        assert_eq!(hover_text(text, 12).as_deref(), Some("string literal"));
+        // Whitespace has nothing to describe.
+        assert_eq!(hover_text(text, 3), None);
+        assert_eq!(hover_text(text, text.len()), None);
+    }
+
+    #[test]
+    fn one_symbol_per_declaration() {
+        let text = "let first: string = \"a\";\nconst é = 2;\nlet = \"no name\";";
+        let symbols = document_symbols(text);
+        let names: Vec<_> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["first", "é"]);
+
+        assert_eq!(symbols[0].kind, SymbolKind::VARIABLE);
+        assert_eq!(symbols[0].detail.as_deref(), Some("string"));
+        assert_eq!(symbols[0].range.start, Position::new(0, 4));
+        assert_eq!(symbols[0].range.end, Position::new(0, 23));
+        assert_eq!(symbols[0].selection_range.end, Position::new(0, 9));
+
+        assert_eq!(symbols[1].kind, SymbolKind::CONSTANT);
+        assert_eq!(symbols[1].selection_range.start, Position::new(1, 6));
+        assert_eq!(symbols[1].selection_range.end, Position::new(1, 7));
+
+        let symbols = document_symbols("let a = 1,\n  bb: int = 2;");
+        assert_eq!(symbols[1].selection_range.start, Position::new(1, 2));
+        assert_eq!(symbols[1].selection_range.end, Position::new(1, 4));
+    }
+
+    #[test]
+    fn multi_line_block_comment_folds() {
+        let text = "let a = \"x\";\n/* one\n   two\n   three */\nlet b = \"y\"; /* short */";
+        let ranges = folding_ranges(text);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!((ranges[0].start_line, ranges[0].end_line), (1, 3));
+        assert_eq!(ranges[0].kind, Some(FoldingRangeKind::Comment));
+    }
+
+    #[test]
+    fn multi_line_declaration_folds() {
+        let text = "let a = \"x\",\n    b = \"y\",\n    c = \"z\"; // trailing\nlet d = \"w\";";
+        let ranges = folding_ranges(text);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!((ranges[0].start_line, ranges[0].end_line), (0, 2));
+        assert_eq!(ranges[0].kind, Some(FoldingRangeKind::Region));
+    }
+
+    #[test]
+    fn rename_covers_the_declaration_and_its_references() {
+        let text = "let count = 1;\nconst total = count, other = 2;\nuse count;";
+        let edits = rename(text, 5, "n").unwrap();
+        let name = table_lex(text)
+            .into_iter()
+            .find(|t| t.kind == SyntaxKind::Ident)
+            .unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].range, range(text, name.span));
+        assert_eq!(edits[0].range, Range::new(Position::new(0, 4), Position::new(0, 9)));
+        assert_eq!(edits[1].range.start, Position::new(1, 14));
+        assert!(edits.iter().all(|e| e.new_text == "n"));
+
+        // From the reference, and from just after the name.
+        assert_eq!(rename(text, 30, "n"), Some(edits.clone()));
+        assert_eq!(rename(text, 9, "n"), Some(edits));
+        // Keywords and the import path are not variables.
+        assert_eq!(rename(text, 1, "n"), None);
+        assert_eq!(rename(text, text.len() - 2, "n"), None);
+
+        // Only the head of a dotted path names a variable.
+        let text = "let a = 1;\nlet b = a.a;";
+        let edits = rename(text, 4, "n").unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[1].range.start, Position::new(1, 8));
+    }
+
+    fn labels(text: &str, offset: usize) -> Vec<String> {
+        completions(text, offset)
+            .into_iter()
+            .map(|c| c.label)
+            .collect()
+    }
+
+    #[test]
+    fn keywords_at_statement_start() {
+        assert_eq!(labels("", 0), ["let", "const", "use"]);
+        let text = "let a = \"x\";\nle";
+        assert_eq!(labels(text, text.len()), ["let", "const", "use"]);
+        assert_eq!(labels(text, text.len() - 3), ["let", "const", "use"]);
+        assert!(completions(text, 4).is_empty());
+    }
+
+    #[test]
+    fn types_after_a_colon() {
+        let text = "let a: ";
+        assert_eq!(labels(text, text.len()), ["string", "int", "float", "list"]);
+        assert_eq!(labels("let a:str", 9), ["string", "int", "float", "list"]);
+        assert_eq!(
+            completions(text, text.len())[0].kind,
+            Some(CompletionItemKind::CLASS)
+        );
+        // Nothing inside a string.
+        assert!(completions("let a: \"", 8).is_empty());
+    }
+
+    #[test]
+    fn completed_types_are_the_ones_the_lexer_and_analyze_accept() {
+        for label in labels("let a: ", 7) {
+            assert_eq!(table_lex(&label)[0].kind, SyntaxKind::Type, "{label}");
+            // A literal, since `analyze` does not check references.
+            let value = match label.as_str() {
+                "string" => "\"s\"",
+                "int" => "1",
+                "float" => "1.5",
+                "list" => "[\"s\"]",
+                other => panic!("no literal for type `{other}`"),
+            };
+            let source = format!("let a: {label} = {value};");
+            let decls = crate::lower_to_ast(&parse_tokens_to_cst(&table_lex(&source)));
+            assert_eq!(decls.len(), 1, "{source}");
+            assert_eq!(decls[0].ty.as_deref(), Some(label.as_str()), "{source}");
+            assert!(crate::analyze(&decls).is_empty(), "{source}");
+        }
+    }
+}
@@ -1,10 +1,97 @@
 #![allow(clippy::unwrap_used)]
 
-fn main() {
-    let input = "let x: string = \"hello\";";
-    table_driven_lexer::table_lex(input).iter().for_each(|tok| {
-        println!("{}", tok);
-    });
+use std::io::Read;
+
+use anyhow::{Context, bail};
+use table_driven_lexer::{
+    SyntaxElement, SyntaxNode, compile, compile_toml, compile_yaml, lower_to_ast,
+    parse_tokens_to_cst, table_lex,
+};
+
+const USAGE: &str =
+    "usage: table-driven-lexer [--tokens | --cst | --json | --format json|yaml|toml] [FILE]";
+
+/// Which pipeline stage to print.
+enum Stage {
+    Tokens,
+    Cst,
+    Compiled(Format),
+}
+
+/// Compilation target for the `Compiled` stage.
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut stage = Stage::Compiled(Format::Json);
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tokens" => stage = Stage::Tokens,
+            "--cst" => stage = Stage::Cst,
+            "--json" => stage = Stage::Compiled(Format::Json),
+            "--format" => {
+                let format = match args.next().as_deref() {
+                    Some("json") => Format::Json,
+                    Some("yaml") => Format::Yaml,
+                    Some("toml") => Format::Toml,
+                    Some(other) => bail!("unknown format `{other}`\n{USAGE}"),
+                    None => bail!("--format needs a value\n{USAGE}"),
+                };
+                stage = Stage::Compiled(format);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            flag if flag.starts_with("--") => bail!("unknown option `{flag}`\n{USAGE}"),
+            _ if path.is_some() => bail!("more than one input file\n{USAGE}"),
+            _ => path = Some(arg),
+        }
+    }
+
+    // Without a path the source is read from stdin.
+    let source = match &path {
+        Some(path) => std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?,
+        None => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .context("reading stdin")?;
+            source
+        }
+    };
+
+    let tokens = table_lex(&source);
+    match stage {
+        Stage::Tokens => tokens.iter().for_each(|tok| println!("{}", tok)),
+        Stage::Cst => print_cst(&parse_tokens_to_cst(&tokens), 0),
+        Stage::Compiled(format) => {
+            let decls = lower_to_ast(&parse_tokens_to_cst(&tokens));
+            match format {
+                Format::Json => println!("{}", compile(&decls)),
+                Format::Yaml => print!("{}", compile_yaml(&decls)),
+                Format::Toml => print!("{}", compile_toml(&decls)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_cst(node: &SyntaxNode, depth: usize) {
+    println!("{:indent$}{}", "", node.kind(), indent = depth * 2);
+    for child in &node.children {
+        match child {
+            SyntaxElement::Node(node) => print_cst(node, depth + 1),
+            SyntaxElement::Token(tok) => {
+                println!("{:indent$}{}", "", tok, indent = (depth + 1) * 2)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -27,8 +114,41 @@ mod qtests {
             let json = compile(&ast);
             serde_json::from_str::<serde_json::Value>(&json).is_ok()
         }
-    }
-}
 
+        fn cst_reproduces_the_source(input: String) -> bool {
+            let cst = parse_tokens_to_cst(&table_lex(&input));
+            cst_to_source(&cst) == input
+        }
+
+        fn lexers_agree(input: String) -> bool {
+            lex(&input) == table_lex(&input)
+        }
 
+        fn spans_tile_the_source(input: String) -> bool {
+            let tokens = table_lex(&input);
+            let contiguous = tokens.windows(2).all(|w| w[0].span.end == w[1].span.start);
+            let first = tokens.first().map_or(0, |t| t.span.start);
+            let last = tokens.last().map_or(0, |t| t.span.end);
+            contiguous && first == 0 && last == input.len()
+        }
 
+        fn formatting_is_idempotent(input: String) -> bool {
+            let once = format_source(&input);
+            format_source(&once) == once
+        }
+
+        fn formatting_preserves_the_ast(input: String) -> bool {
+            let formatted = format_source(&input);
+            summary(&input) == summary(&formatted)
+        }
+    }
+
+    /// Everything `lower_to_ast` extracts except the spans, which
+    /// formatting is expected to move.
+    fn summary(source: &str) -> Vec<(String, bool, Option<String>, DeclValue, SyntaxKind)> {
+        lower_to_ast(&parse_tokens_to_cst(&table_lex(source)))
+            .into_iter()
+            .map(|d| (d.name, d.mutable, d.ty, d.value, d.value_kind))
+            .collect()
+    }
+}
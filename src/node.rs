@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock, Weak};
 
-use crate::{SyntaxKind, Token};
+use crate::{Span, SyntaxKind, Token, TokenData, push_source};
 
 
 #[derive(Debug, Clone)]
@@ -11,15 +11,53 @@ pub enum SyntaxElement {
 
 pub type SyntaxNode = Arc<SyntaxNodeData>;
 
+/// A node of the concrete syntax tree.
+///
+/// Parents own their children through `Arc`; a child refers back to its
+/// parent through a `Weak`, so trees never form reference cycles. The back
+/// link is set once, when the parent is created with [`SyntaxNodeData::build`]
+/// (as the parser and [`SyntaxTreeBuilder`] do), and [`parent`] returns
+/// `None` once the parent has been dropped. A subtree shared between trees
+/// keeps the parent it was first built into.
+///
+/// [`parent`]: SyntaxNodeData::parent
 #[derive(Debug, Clone)]
 pub struct SyntaxNodeData {
     pub kind: SyntaxKind,
     pub children: Vec<SyntaxElement>,
+    parent: OnceLock<Weak<SyntaxNodeData>>,
 }
 
 impl SyntaxNodeData {
+    /// A detached node. Its children do not learn their parent; use
+    /// [`SyntaxNodeData::build`] for that.
     pub fn new(kind: SyntaxKind, children: Vec<SyntaxElement>) -> Self {
-        SyntaxNodeData { kind, children }
+        SyntaxNodeData {
+            kind,
+            children,
+            parent: OnceLock::new(),
+        }
+    }
+
+    /// Creates the node and links each child node back to it.
+    pub fn build(kind: SyntaxKind, children: Vec<SyntaxElement>) -> SyntaxNode {
+        let node = Arc::new(SyntaxNodeData::new(kind, children));
+        for child in node.child_nodes() {
+            let _ = child.parent.set(Arc::downgrade(&node));
+        }
+        node
+    }
+
+    /// The node containing this one, if it was linked by
+    /// [`SyntaxNodeData::build`] and is still alive.
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.parent.get().and_then(Weak::upgrade)
+    }
+
+    /// This node followed by its parent, grandparent, and so on up to the
+    /// root.
+    pub fn ancestors(self: &Arc<Self>) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(Some(self.clone()), |node| node.parent())
     }
 
     pub fn tokens(&self) -> Vec<&Token> {
@@ -32,6 +70,18 @@ impl SyntaxNodeData {
             .collect()
     }
 
+    /// Every token in the subtree, in source order.
+    pub fn descendant_tokens(&self) -> Vec<&Token> {
+        let mut out = Vec::new();
+        for child in &self.children {
+            match child {
+                SyntaxElement::Token(tok) => out.push(tok),
+                SyntaxElement::Node(node) => out.extend(node.descendant_tokens()),
+            }
+        }
+        out
+    }
+
     pub fn child_nodes(&self) -> Vec<&SyntaxNode> {
         self.children
             .iter()
@@ -47,3 +97,271 @@ impl SyntaxNodeData {
     }
 }
 
+/// Builds a tree top-down, for tests and tools that construct CSTs by
+/// hand. Spans are assigned as if the token texts were laid out back to
+/// back, the way [`cst_to_source`] prints them.
+///
+/// ```
+/// use table_driven_lexer::{SyntaxKind, SyntaxTreeBuilder, cst_to_source};
+///
+/// let mut builder = SyntaxTreeBuilder::new();
+/// builder.start_node(SyntaxKind::Root);
+/// builder.token(SyntaxKind::Ident, "x");
+/// builder.finish_node();
+/// assert_eq!(cst_to_source(&builder.finish()), "x");
+/// ```
+#[derive(Debug, Default)]
+pub struct SyntaxTreeBuilder {
+    parents: Vec<(SyntaxKind, Vec<SyntaxElement>)>,
+    root: Option<SyntaxNode>,
+    offset: usize,
+}
+
+impl SyntaxTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, Vec::new()));
+    }
+
+    /// Adds a token to the current node. As in the lexer, a `StringLiteral`
+    /// text is the unquoted value and its span covers the quotes.
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        let quotes = if kind == SyntaxKind::StringLiteral { 2 } else { 0 };
+        let span = Span::new(self.offset, self.offset + text.len() + quotes);
+        self.offset = span.end;
+        let token = TokenData {
+            kind,
+            text: text.to_string().into(),
+            span,
+            symbol: None,
+            raw: None,
+        };
+        self.current().push(SyntaxElement::Token(Arc::new(token)));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.parents.pop().expect("finish_node without start_node");
+        let node = SyntaxNodeData::build(kind, children);
+        match self.parents.last_mut() {
+            Some((_, siblings)) => siblings.push(SyntaxElement::Node(node)),
+            None => {
+                assert!(self.root.is_none(), "a tree has a single root");
+                self.root = Some(node);
+            }
+        }
+    }
+
+    /// The finished tree. Panics if a node is still open or none was built.
+    pub fn finish(self) -> SyntaxNode {
+        assert!(self.parents.is_empty(), "unfinished node");
+        self.root.expect("no node was built")
+    }
+
+    fn current(&mut self) -> &mut Vec<SyntaxElement> {
+        &mut self.parents.last_mut().expect("token outside of a node").1
+    }
+}
+
+/// Hooks for a pre-order walk over a tree with [`walk`]. Both default to
+/// doing nothing, so a visitor implements only the ones it needs.
+pub trait Visitor {
+    /// Called for each node before any of its children.
+    fn visit_node(&mut self, _node: &SyntaxNode) {}
+
+    fn visit_token(&mut self, _tok: &Token) {}
+}
+
+/// Visits `node` and then its children in order, recursing into child
+/// nodes.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, node: &SyntaxNode) {
+    visitor.visit_node(node);
+    for child in &node.children {
+        match child {
+            SyntaxElement::Node(node) => walk(visitor, node),
+            SyntaxElement::Token(tok) => visitor.visit_token(tok),
+        }
+    }
+}
+
+/// Reproduces the source a tree was parsed from by concatenating its tokens
+/// in order. Exact as long as the tree holds every token, trivia included.
+pub fn cst_to_source(root: &SyntaxNode) -> String {
+    fn walk(node: &SyntaxNodeData, out: &mut String) {
+        for child in &node.children {
+            match child {
+                SyntaxElement::Node(node) => walk(node, out),
+                SyntaxElement::Token(tok) => push_source(out, tok),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    walk(root, &mut out);
+    out
+}
+
+/// Dumps a tree as JSON for inspection in other tools. A node becomes
+/// `{"kind": ..., "children": [...]}` and a token `{"kind": ..., "text": ...}`,
+/// with kinds as `Display` prints them and a token's text as it appears in
+/// the source. Unlike [`compile`](crate::compile), nothing is lowered.
+#[cfg(feature = "serde")]
+pub fn cst_to_json(root: &SyntaxNode) -> String {
+    fn element(el: &SyntaxElement) -> serde_json::Value {
+        match el {
+            SyntaxElement::Node(node) => node_value(node),
+            SyntaxElement::Token(tok) => {
+                let mut text = String::new();
+                push_source(&mut text, tok);
+                serde_json::json!({ "kind": tok.kind.to_string(), "text": text })
+            }
+        }
+    }
+
+    fn node_value(node: &SyntaxNodeData) -> serde_json::Value {
+        let children: Vec<_> = node.children.iter().map(element).collect();
+        serde_json::json!({ "kind": node.kind.to_string(), "children": children })
+    }
+
+    serde_json::to_string_pretty(&node_value(root)).expect("a JSON value always serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lower_to_ast;
+
+    #[test]
+    fn visitor_counts_identifiers() {
+        #[derive(Default)]
+        struct Counter {
+            idents: usize,
+            nodes: Vec<SyntaxKind>,
+        }
+
+        impl Visitor for Counter {
+            fn visit_node(&mut self, node: &SyntaxNode) {
+                self.nodes.push(node.kind());
+            }
+
+            fn visit_token(&mut self, tok: &Token) {
+                if tok.kind == SyntaxKind::Ident {
+                    self.idents += 1;
+                }
+            }
+        }
+
+        let root = crate::parse_tokens_to_cst(&crate::table_lex("let a = b, c = 1;\nuse d::e;"));
+        let mut counter = Counter::default();
+        walk(&mut counter, &root);
+        assert_eq!(counter.idents, 5);
+        // Parents come before their children.
+        assert_eq!(
+            counter.nodes,
+            [
+                SyntaxKind::Root,
+                SyntaxKind::VarDecl,
+                SyntaxKind::Value,
+                SyntaxKind::Value,
+                SyntaxKind::ImportStmt,
+            ]
+        );
+    }
+
+    #[test]
+    fn built_var_decl_lowers() {
+        let mut builder = SyntaxTreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.start_node(SyntaxKind::VarDecl);
+        for (kind, text) in [
+            (SyntaxKind::Let, "let"),
+            (SyntaxKind::Whitespace, " "),
+            (SyntaxKind::Ident, "x"),
+            (SyntaxKind::Colon, ":"),
+            (SyntaxKind::Whitespace, " "),
+            (SyntaxKind::Type, "string"),
+            (SyntaxKind::Whitespace, " "),
+            (SyntaxKind::Equal, "="),
+            (SyntaxKind::Whitespace, " "),
+        ] {
+            builder.token(kind, text);
+        }
+        builder.start_node(SyntaxKind::Value);
+        builder.token(SyntaxKind::StringLiteral, "hi");
+        builder.finish_node();
+        builder.token(SyntaxKind::Semicolon, ";");
+        builder.finish_node();
+        builder.finish_node();
+        let root = builder.finish();
+
+        let source = "let x: string = \"hi\";";
+        assert_eq!(cst_to_source(&root), source);
+        let decls = lower_to_ast(&root);
+        assert_eq!(decls.len(), 1);
+        assert_eq!((decls[0].name.as_str(), decls[0].value.as_scalar()), ("x", Some("hi")));
+        assert_eq!(&source[decls[0].span.start..decls[0].span.end], "x: string = \"hi\"");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn cst_dumps_to_json() {
+        let source = "let a = \"x\";";
+        let root = crate::parse_tokens_to_cst(&crate::table_lex(source));
+        let json: serde_json::Value = serde_json::from_str(&cst_to_json(&root)).unwrap();
+        assert_eq!(json["kind"], "ROOT");
+        let decl = &json["children"][0];
+        assert_eq!(decl["kind"], "VARDECL");
+        assert_eq!(decl["children"][0], serde_json::json!({ "kind": "LET", "text": "let" }));
+        let value = decl["children"].as_array().unwrap().iter().find(|c| c["kind"] == "VALUE");
+        assert_eq!(value.unwrap()["children"][0]["text"], "\"x\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "unfinished node")]
+    fn finishing_with_an_open_node_panics() {
+        let mut builder = SyntaxTreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.finish();
+    }
+
+    #[test]
+    fn parents_lead_back_to_the_root() {
+        let source = "let a = \"x\";\nlet b = \"y\";";
+        let root = crate::parse_tokens_to_cst(&crate::table_lex(source));
+        assert!(root.parent().is_none());
+
+        // The declaration holding the identifier `b`.
+        let decl = root
+            .child_nodes()
+            .into_iter()
+            .find(|n| n.tokens().iter().any(|t| t.text == "b"))
+            .unwrap()
+            .clone();
+        let kinds: Vec<_> = decl.ancestors().map(|n| n.kind()).collect();
+        assert_eq!(kinds, [SyntaxKind::VarDecl, SyntaxKind::Root]);
+        assert!(Arc::ptr_eq(&decl.parent().unwrap(), &root));
+
+        // The back link does not keep the parent alive.
+        drop(root);
+        assert!(decl.parent().is_none());
+    }
+
+    #[test]
+    fn built_trees_link_nested_nodes() {
+        let mut builder = SyntaxTreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.start_node(SyntaxKind::VarDecl);
+        builder.start_node(SyntaxKind::Error);
+        builder.token(SyntaxKind::Ident, "x");
+        builder.finish_node();
+        builder.finish_node();
+        builder.finish_node();
+        let root = builder.finish();
+
+        let error = root.child_nodes()[0].child_nodes()[0].clone();
+        let kinds: Vec<_> = error.ancestors().map(|n| n.kind()).collect();
+        assert_eq!(kinds, [SyntaxKind::Error, SyntaxKind::VarDecl, SyntaxKind::Root]);
+    }
+}
@@ -1,90 +1,29 @@
-use crate::{SyntaxKind, Token, TokenData};
+use crate::{Token, table_lex};
 
+/// The original hand-written tokenizer, kept as an alias for
+/// [`table_lex`] so the two entry points can't disagree. It used to
+/// collapse whitespace runs to a single space and knew no multi-character
+/// operators.
 pub fn lex(source: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
+    table_lex(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Span, SyntaxKind};
 
-    while let Some(&ch) = chars.peek() {
-        match ch {
-            c if c.is_whitespace() => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::Whitespace,
-                    text: " ".into(),
-                }));
-                chars.next();
-            }
-            ':' => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::Colon,
-                    text: ":".into(),
-                }));
-                chars.next();
-            }
-            '\n' => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::NewLine,
-                    text: "\n".into(),
-                }));
-                chars.next();
-            }
-            '=' => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::Equal,
-                    text: "=".into(),
-                }));
-                chars.next();
-            }
-            ';' => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::Semicolon,
-                    text: ";".into(),
-                }));
-                chars.next();
-            }
-            '"' => {
-                chars.next();
-                let mut value = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c == '"' {
-                        chars.next();
-                        break;
-                    }
-                    value.push(c);
-                    chars.next();
-                }
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::StringLiteral,
-                    text: value,
-                }));
-            }
-            c if c.is_alphabetic() => {
-                let mut ident = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_alphanumeric() || c == '_' {
-                        ident.push(c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                let kind = match ident.as_str() {
-                    "let" => SyntaxKind::Let,
-                    "string" => SyntaxKind::Type,
-                    _ => SyntaxKind::Ident,
-                };
-                tokens.push(Token::new(TokenData { kind, text: ident }));
-            }
-            _ => {
-                tokens.push(Token::new(TokenData {
-                    kind: SyntaxKind::Error,
-                    text: ch.to_string(),
-                }));
-                chars.next();
-            }
-        }
+    #[test]
+    fn lex_matches_table_lex() {
+        let source = "let a::b\t:=  \"x\"; // c\nconst n => 1.5;";
+        assert_eq!(lex(source), table_lex(source));
     }
 
-    tokens
+    #[test]
+    fn whitespace_runs_keep_their_text() {
+        let tokens = lex("a   b");
+        assert_eq!(tokens[1].kind, SyntaxKind::Whitespace);
+        assert_eq!(tokens[1].text, "   ");
+        assert_eq!(tokens[1].span, Span::new(1, 4));
+    }
 }
-
-
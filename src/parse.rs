@@ -1,131 +1,1467 @@
+use std::collections::HashSet;
 
-use crate::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxNodeData, Token};
+use thiserror::Error;
+
+use crate::{
+    Diagnostic, Severity, Span, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxNodeData, Token,
+    TokenExt, TokenStream, VarDeclNode, fixed_text, string_contents_span,
+};
+
+/// Parser settings.
+#[derive(Debug, Clone)]
+pub struct ParseConfig {
+    /// Token kind that ends a statement. A `NewLine` terminator is no longer
+    /// treated as trivia.
+    pub terminator: SyntaxKind,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        ParseConfig {
+            terminator: SyntaxKind::Semicolon,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "miette", derive(miette::Diagnostic))]
+pub enum ParseError {
+    /// Content between statements that does not start a statement.
+    #[error("unexpected {kind} at {}..{}", span.start, span.end)]
+    UnexpectedToken {
+        kind: SyntaxKind,
+        #[cfg_attr(feature = "miette", label("expected a statement"))]
+        span: Span,
+    },
+    /// A statement that ends without its terminator. The span is empty and
+    /// sits where the terminator should have been.
+    #[error("missing {} at {}", spelling(*expected), span.start)]
+    MissingTerminator {
+        expected: SyntaxKind,
+        #[cfg_attr(feature = "miette", label("expected here"))]
+        span: Span,
+    },
+    /// A statement missing a piece it needs: a token, spelled as in other
+    /// messages, or "a value". `found` is `None` at the end of the input,
+    /// where the span is empty.
+    #[error(
+        "expected {expected}, found {}",
+        found.map_or_else(|| "end of input".to_string(), spelling)
+    )]
+    Expected {
+        expected: String,
+        found: Option<SyntaxKind>,
+        #[cfg_attr(feature = "miette", label("expected here"))]
+        span: Span,
+    },
+    /// A dotted path ending in a `.`, which is the span.
+    #[error("expected a name after '.' at {}", span.start)]
+    MissingPathSegment {
+        #[cfg_attr(feature = "miette", label("expected a name after this"))]
+        span: Span,
+    },
+}
+
+/// How an error message names a token kind: quoted source text when the
+/// kind has a fixed spelling, its name otherwise.
+fn spelling(kind: SyntaxKind) -> String {
+    match fixed_text(kind) {
+        Some(text) => format!("'{}'", text.escape_debug()),
+        None => kind.to_string(),
+    }
+}
 
 pub fn parse_tokens_to_cst(tokens: &[Token]) -> SyntaxNode {
-    let mut i = 0;
-    let mut decls = Vec::new();
+    parse_tokens_with_config(tokens, &ParseConfig::default())
+}
+
+pub fn parse_tokens_with_config(tokens: &[Token], config: &ParseConfig) -> SyntaxNode {
+    parse_tokens_with_errors(tokens, config).0
+}
+
+/// Parses like [`parse_tokens_with_config`], also returning the errors
+/// found. Stray content is wrapped in an `Error` node and parsing resumes at
+/// the next statement.
+pub fn parse_tokens_with_errors(
+    tokens: &[Token],
+    config: &ParseConfig,
+) -> (SyntaxNode, Vec<ParseError>) {
+    Parser::new(tokens, config).parse()
+}
 
-    while i < tokens.len() {
-        if tokens.get(i).map(|t| &t.kind) != Some(&SyntaxKind::Let) {
-            break;
+/// Builds the CST for a token slice, collecting errors as it goes instead
+/// of stopping at the first one. Each error is recorded where it is found
+/// and parsing carries on, so the tree always covers the whole input.
+pub struct Parser<'a> {
+    stream: TokenStream<'a>,
+    config: &'a ParseConfig,
+    errors: Vec<ParseError>,
+    eof: Option<&'a Token>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token], config: &'a ParseConfig) -> Self {
+        // A trailing `Eof` only confirms where the input ends; it closes the root.
+        let (tokens, eof) = match tokens.split_last() {
+            Some((last, rest)) if last.kind() == SyntaxKind::Eof => (rest, Some(last)),
+            _ => (tokens, None),
+        };
+        Parser {
+            stream: TokenStream::new(tokens),
+            config,
+            errors: Vec::new(),
+            eof,
         }
+    }
 
-        let mut children = Vec::new();
+    /// The `Root` node and every error recorded while building it.
+    pub fn parse(mut self) -> (SyntaxNode, Vec<ParseError>) {
+        let root = self.parse_root();
+        (root, self.errors)
+    }
+}
 
-        children.push(SyntaxElement::Token(tokens[i].clone())); // let
-        i += 1;
+impl Parser<'_> {
+    fn parse_root(&mut self) -> SyntaxNode {
+        let mut items = Vec::new();
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::Ident {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+        loop {
+            self.eat_trivia(&mut items);
+            // A line break terminating nothing is a blank line, not stray
+            // content, even when line breaks end statements.
+            if self.stream.at(SyntaxKind::NewLine) {
+                self.bump(&mut items);
+                continue;
             }
+            let Some(tok) = self.stream.peek() else {
+                break;
+            };
+            let item = match self.parse_statement(tok.kind()) {
+                Some(stmt) => stmt,
+                None => self.parse_stray(),
+            };
+            items.push(SyntaxElement::Node(item));
+        }
+        items.extend(self.eof.cloned().map(SyntaxElement::Token));
+
+        SyntaxNodeData::build(SyntaxKind::Root, items)
+    }
+
+    /// Dispatches on the keyword starting a statement. New statement kinds
+    /// add an arm here and a sub-parser returning their node.
+    fn parse_statement(&mut self, keyword: SyntaxKind) -> Option<SyntaxNode> {
+        match keyword {
+            SyntaxKind::Let | SyntaxKind::Const => Some(self.parse_var_decl()),
+            SyntaxKind::Use => Some(self.parse_import()),
+            _ => None,
         }
+    }
+
+    /// Wraps content that does not start a statement in an `Error` node.
+    fn parse_stray(&mut self) -> SyntaxNode {
+        let tok = self.stream.peek().expect("stray content at the cursor");
+        self.errors.push(ParseError::UnexpectedToken {
+            kind: tok.kind(),
+            span: tok.span,
+        });
+        let mut children = Vec::new();
+        self.recover(&mut children);
+        SyntaxNodeData::build(SyntaxKind::Error, children)
+    }
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::Colon {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+    /// `(let | const) name (: type)? = value (, name (: type)? = value)* <terminator>`
+    fn parse_var_decl(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children); // let/const
+        let errors = self.errors.len();
+
+        loop {
+            self.expect(SyntaxKind::Ident, &mut children);
+            if self.eat(SyntaxKind::Colon, &mut children) {
+                self.eat(SyntaxKind::Type, &mut children);
+            }
+            if self.expect(SyntaxKind::Equal, &mut children) && !self.parse_value(&mut children) {
+                self.expected("a value".to_string(), &children);
+            }
+            if !self.eat(SyntaxKind::Comma, &mut children) {
+                break;
             }
         }
+        // One error per statement is enough; a broken binding rarely ends
+        // where its terminator should be.
+        if !self.eat(self.config.terminator, &mut children) && self.errors.len() == errors {
+            self.missing_terminator(&children);
+        }
+        self.eat_trailing_trivia(&mut children);
+
+        SyntaxNodeData::build(SyntaxKind::VarDecl, children)
+    }
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::Type {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+    /// A literal or a bare identifier naming another variable, wrapped in a
+    /// `Value` node, or an array or dotted path. The trivia in front of it
+    /// stays outside the node.
+    fn parse_value(&mut self, children: &mut Vec<SyntaxElement>) -> bool {
+        let j = self.next_significant();
+        if self.stream.nth(j).is_some_and(|t| t.kind() == SyntaxKind::LBracket) {
+            self.parse_array(children);
+            return true;
+        }
+        let dot = self.next_significant_from(j + 1);
+        if self.stream.nth(j).is_some_and(|t| t.kind() == SyntaxKind::Ident)
+            && self.stream.nth(dot).is_some_and(|t| t.kind() == SyntaxKind::Dot)
+        {
+            self.parse_path(children);
+            return true;
+        }
+        if !self.stream.nth(j).is_some_and(|t| is_value(t.kind())) {
+            return false;
+        }
+        self.eat_trivia(children);
+        let mut value = Vec::new();
+        self.bump(&mut value);
+        children.push(SyntaxElement::Node(SyntaxNodeData::build(SyntaxKind::Value, value)));
+        true
+    }
+
+    /// `[ (literal (, literal)* ,?)? ]`, wrapped in an `ArrayValue` node. An
+    /// array cut short by anything else ends where the problem starts,
+    /// without its `]`.
+    fn parse_array(&mut self, children: &mut Vec<SyntaxElement>) {
+        self.eat_trivia(children);
+        let mut items = Vec::new();
+        self.eat(SyntaxKind::LBracket, &mut items);
+        while !self.eat(SyntaxKind::RBracket, &mut items)
+            && self.eat_if(|kind| literal_type(kind).is_some(), &mut items)
+        {
+            if !self.eat(SyntaxKind::Comma, &mut items) {
+                self.eat(SyntaxKind::RBracket, &mut items);
+                break;
             }
         }
+        children.push(SyntaxElement::Node(SyntaxNodeData::build(
+            SyntaxKind::ArrayValue,
+            items,
+        )));
+    }
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::Equal {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+    /// `ident (. ident)*`, wrapped in a `Path` node. A `.` without a name
+    /// after it is reported and ends the path.
+    fn parse_path(&mut self, children: &mut Vec<SyntaxElement>) {
+        self.eat_trivia(children);
+        let mut items = Vec::new();
+        self.eat(SyntaxKind::Ident, &mut items);
+        while let Some(dot) = self.stream.nth(self.next_significant())
+            && self.eat(SyntaxKind::Dot, &mut items)
+        {
+            if !self.eat(SyntaxKind::Ident, &mut items) {
+                self.errors.push(ParseError::MissingPathSegment { span: dot.span });
+                break;
             }
         }
+        children.push(SyntaxElement::Node(SyntaxNodeData::build(
+            SyntaxKind::Path,
+            items,
+        )));
+    }
+
+    /// `use "path" <terminator>` or `use name (:: name)* <terminator>`
+    fn parse_import(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children); // use
+
+        let has_path = self.eat(SyntaxKind::StringLiteral, &mut children)
+            || self.eat_module_path(&mut children);
+        if !(has_path && self.eat(self.config.terminator, &mut children)) {
+            self.recover(&mut children);
+        }
+        self.eat_trailing_trivia(&mut children);
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::StringLiteral {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+        SyntaxNodeData::build(SyntaxKind::ImportStmt, children)
+    }
+
+    /// Records a missing terminator just after the last token of
+    /// `children`. When stray content follows instead of a new statement,
+    /// the error for that content covers it; a missing line break at the
+    /// very end of the input is not an error at all.
+    fn missing_terminator(&mut self, children: &[SyntaxElement]) {
+        match self.stream.nth(self.next_significant()) {
+            None if self.config.terminator == SyntaxKind::NewLine => return,
+            Some(tok) if !tok.kind().is_keyword() => return,
+            _ => {}
+        }
+        let end = end_of(children);
+        self.errors.push(ParseError::MissingTerminator {
+            expected: self.config.terminator,
+            span: Span::new(end, end),
+        });
+    }
+
+    /// Like [`Parser::eat`], recording an `Expected` error for whatever is
+    /// there instead.
+    fn expect(&mut self, kind: SyntaxKind, children: &mut Vec<SyntaxElement>) -> bool {
+        if self.eat(kind, children) {
+            return true;
+        }
+        self.expected(spelling(kind), children);
+        false
+    }
+
+    /// Records an `Expected` error at the next significant token, or just
+    /// after `children` at the end of the input.
+    fn expected(&mut self, expected: String, children: &[SyntaxElement]) {
+        let found = self.stream.nth(self.next_significant());
+        let end = end_of(children);
+        let span = found.map_or(Span::new(end, end), |t| t.span);
+        self.errors.push(ParseError::Expected {
+            expected,
+            found: found.map(|t| t.kind()),
+            span,
+        });
+    }
+
+    fn eat_module_path(&mut self, children: &mut Vec<SyntaxElement>) -> bool {
+        if !self.eat(SyntaxKind::Ident, children) {
+            return false;
+        }
+        while self.eat(SyntaxKind::DoubleColon, children) {
+            if !self.eat(SyntaxKind::Ident, children) {
+                return false;
             }
         }
+        true
+    }
 
-        if let Some(tok) = tokens.get(i) {
-            if tok.kind == SyntaxKind::Semicolon {
-                children.push(SyntaxElement::Token(tok.clone()));
-                i += 1;
+    /// Skips the rest of a malformed statement: through the next terminator,
+    /// or up to the keyword starting the next statement.
+    fn recover(&mut self, children: &mut Vec<SyntaxElement>) {
+        while let Some(tok) = self.stream.peek() {
+            if tok.kind().is_keyword() {
+                break;
+            }
+            self.bump(children);
+            if tok.kind() == self.config.terminator {
+                break;
             }
         }
+    }
+
+    fn is_trivia(&self, kind: SyntaxKind) -> bool {
+        kind.is_trivia() && kind != self.config.terminator
+    }
 
-        decls.push(SyntaxElement::Node(
-            SyntaxNodeData {
-                kind: SyntaxKind::VarDecl,
-                children,
+    /// Attaches the trivia after a statement, up to and including the end of
+    /// its line, to that statement, so a same-line comment stays with the
+    /// code it annotates instead of leading the next statement. A block
+    /// comment spanning lines, or a doc comment, is left for whatever
+    /// follows.
+    fn eat_trailing_trivia(&mut self, children: &mut Vec<SyntaxElement>) {
+        while let Some(tok) = self.stream.peek() {
+            let same_line = match tok.kind() {
+                SyntaxKind::Whitespace | SyntaxKind::LineComment => true,
+                SyntaxKind::BlockComment => !tok.text().contains('\n'),
+                SyntaxKind::NewLine => self.is_trivia(tok.kind()),
+                _ => false,
+            };
+            if !same_line {
+                break;
             }
-            .into(),
-        ));
+            self.bump(children);
+            if tok.kind() == SyntaxKind::NewLine {
+                break;
+            }
+        }
     }
 
-    SyntaxNodeData::new(SyntaxKind::Root, decls).into()
+    fn eat_trivia(&mut self, children: &mut Vec<SyntaxElement>) {
+        while self.stream.peek().is_some_and(|t| self.is_trivia(t.kind())) {
+            self.bump(children);
+        }
+    }
+
+    /// Moves the token at the cursor into `children`.
+    fn bump(&mut self, children: &mut Vec<SyntaxElement>) {
+        children.extend(self.stream.bump().cloned().map(SyntaxElement::Token));
+    }
+
+    /// How far ahead of the cursor the first non-trivia token is, or the
+    /// end of the input. Nothing is consumed; `eat` records the skipped
+    /// trivia once it accepts the token.
+    fn next_significant(&self) -> usize {
+        self.next_significant_from(0)
+    }
+
+    /// Like [`Parser::next_significant`], looking from `j` tokens ahead.
+    fn next_significant_from(&self, mut j: usize) -> usize {
+        while self.stream.nth(j).is_some_and(|t| self.is_trivia(t.kind())) {
+            j += 1;
+        }
+        j
+    }
+
+    /// Consumes the next significant token, together with the trivia in
+    /// front of it, if it has the expected kind. Nothing is consumed
+    /// otherwise.
+    fn eat(&mut self, kind: SyntaxKind, children: &mut Vec<SyntaxElement>) -> bool {
+        self.eat_if(|k| k == kind, children)
+    }
+
+    /// Like [`Parser::eat`], for any kind matching `pred`.
+    fn eat_if(
+        &mut self,
+        pred: impl Fn(SyntaxKind) -> bool,
+        children: &mut Vec<SyntaxElement>,
+    ) -> bool {
+        let j = self.next_significant();
+        if !self.stream.nth(j).is_some_and(|t| pred(t.kind())) {
+            return false;
+        }
+        self.eat_trivia(children);
+        self.bump(children);
+        true
+    }
+}
+
+/// Where the last token of `children` ends, or 0 if there is none.
+fn end_of(children: &[SyntaxElement]) -> usize {
+    children
+        .iter()
+        .rev()
+        .find_map(|el| match el {
+            SyntaxElement::Token(tok) => Some(tok.span.end),
+            SyntaxElement::Node(node) => node.descendant_tokens().last().map(|t| t.span.end),
+        })
+        .unwrap_or(0)
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VarDecl {
     pub name: String,
-    pub ty: String,
-    pub value: String,
+    /// `true` for `let`, `false` for `const`.
+    pub mutable: bool,
+    /// Declared type, if the binding has an annotation.
+    pub ty: Option<String>,
+    pub value: DeclValue,
+    /// `StringLiteral`, `IntLiteral`, `FloatLiteral`, `BoolLiteral`,
+    /// `Ident` for a reference to another variable, `Path` for a dotted
+    /// path joined with `.`, or `ArrayValue`.
+    pub value_kind: SyntaxKind,
+    /// From the start of the name to the end of the value.
+    pub span: Span,
+    pub name_span: Span,
+    /// The type annotation, if there is one.
+    pub type_span: Option<Span>,
+    /// The source `value` was read from: a string literal's contents
+    /// without the quotes, or the whole value otherwise.
+    pub value_span: Span,
 }
 
-pub fn lower_to_ast(root: &SyntaxNode) -> Vec<VarDecl> {
-    let mut decls = Vec::new();
+/// The value of a binding. String literals are stored without their quotes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeclValue {
+    /// A single literal or reference.
+    Scalar(String),
+    /// The elements of an array literal.
+    List(Vec<String>),
+}
+
+impl DeclValue {
+    /// The text of a scalar value, or `None` for a list.
+    pub fn as_scalar(&self) -> Option<&str> {
+        match self {
+            DeclValue::Scalar(text) => Some(text),
+            DeclValue::List(_) => None,
+        }
+    }
+}
+
+impl PartialEq<&str> for DeclValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_scalar() == Some(*other)
+    }
+}
+
+#[derive(Debug)]
+pub struct Import {
+    /// The string path, or the module path joined with `::`.
+    pub path: String,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    VarDecl(VarDecl),
+    Import(Import),
+}
+
+/// Lowers every statement in source order. Malformed statements are
+/// skipped.
+pub fn lower_statements(root: &SyntaxNode) -> Vec<Stmt> {
+    lower_statements_with_config(root, &ParseConfig::default())
+}
+
+/// Like [`lower_statements`], for a tree parsed with `config`.
+pub fn lower_statements_with_config(root: &SyntaxNode, config: &ParseConfig) -> Vec<Stmt> {
+    let mut stmts = Vec::new();
     for node in root.child_nodes() {
-        if node.kind() != SyntaxKind::VarDecl {
-            continue;
+        match node.kind() {
+            SyntaxKind::VarDecl => {
+                stmts.extend(lower_var_decl(node).into_iter().map(Stmt::VarDecl))
+            }
+            SyntaxKind::ImportStmt => {
+                stmts.extend(lower_import(node, config.terminator).map(Stmt::Import))
+            }
+            _ => {}
         }
+    }
+    stmts
+}
 
-        let tokens = node.tokens();
-        let name = tokens
-            .iter()
-            .find(|t| t.kind == SyntaxKind::Ident)
-            .unwrap()
-            .text
-            .clone();
-        let ty = tokens
-            .iter()
-            .find(|t| t.kind == SyntaxKind::Type)
-            .unwrap()
-            .text
-            .clone();
-        let value = tokens
-            .iter()
-            .find(|t| t.kind == SyntaxKind::StringLiteral)
-            .unwrap()
-            .text
-            .clone();
+/// The variable declarations of the program, one per binding.
+pub fn lower_to_ast(root: &SyntaxNode) -> Vec<VarDecl> {
+    lower_statements(root)
+        .into_iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::VarDecl(decl) => Some(decl),
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn lower_var_decl(node: &SyntaxNode) -> Vec<VarDecl> {
+    let mut decls = Vec::new();
+    let Some(decl) = VarDeclNode::cast(node.clone()) else {
+        return decls;
+    };
+
+    // Each comma-separated binding lowers to its own declaration.
+    let mutable = decl.syntax().tokens()[0].kind == SyntaxKind::Let;
+    let is_comma =
+        |el: &SyntaxElement| matches!(el, SyntaxElement::Token(t) if *t == SyntaxKind::Comma);
+    for binding in decl.syntax().children.split(is_comma) {
+        let find = |kind| {
+            binding.iter().find_map(|el| match el {
+                SyntaxElement::Token(t) if t.kind() == kind => Some(t),
+                _ => None,
+            })
+        };
+        let value_node = binding.iter().find_map(|el| match el {
+            SyntaxElement::Node(n) => Some(n),
+            _ => None,
+        });
 
-        decls.push(VarDecl { name, ty, value });
+        // Incomplete bindings have nothing meaningful to lower.
+        let (Some(name), Some((value, value_kind, span))) =
+            (find(SyntaxKind::Ident), value_node.and_then(lower_value))
+        else {
+            continue;
+        };
+        // Like `value`, a string's span leaves out the quotes.
+        let value_span = match value_node.and_then(|n| n.tokens().first().cloned()) {
+            Some(tok) if tok.kind() == SyntaxKind::StringLiteral => string_contents_span(tok),
+            _ => span,
+        };
+        let ty = find(SyntaxKind::Type);
+
+        decls.push(VarDecl {
+            name: name.text().to_string(),
+            mutable,
+            ty: ty.map(|t| t.text().to_string()),
+            value,
+            value_kind,
+            span: name.span.merge(span),
+            name_span: name.span,
+            type_span: ty.map(|t| t.span),
+            value_span,
+        });
     }
 
     decls
 }
 
-pub fn analyze(decls: &[VarDecl]) {
+/// The value, value kind and span of a `Value`, `ArrayValue` or `Path`
+/// node. An array missing its `]` and a path ending in `.` have no value.
+fn lower_value(node: &SyntaxNode) -> Option<(DeclValue, SyntaxKind, Span)> {
+    let tokens = node.tokens();
+    match node.kind() {
+        SyntaxKind::Value => {
+            let tok = tokens.first()?;
+            Some((DeclValue::Scalar(tok.text().to_string()), tok.kind(), tok.span))
+        }
+        SyntaxKind::ArrayValue => {
+            let (first, last) = (tokens.first()?, tokens.last()?);
+            if last.kind() != SyntaxKind::RBracket {
+                return None;
+            }
+            let items = tokens
+                .iter()
+                .filter(|t| literal_type(t.kind()).is_some())
+                .map(|t| t.text().to_string())
+                .collect();
+            Some((
+                DeclValue::List(items),
+                SyntaxKind::ArrayValue,
+                first.span.merge(last.span),
+            ))
+        }
+        SyntaxKind::Path => {
+            let (first, last) = (tokens.first()?, tokens.last()?);
+            if last.kind() != SyntaxKind::Ident {
+                return None;
+            }
+            let path: Vec<_> = tokens
+                .iter()
+                .filter(|t| t.kind() == SyntaxKind::Ident)
+                .map(|t| t.text())
+                .collect();
+            Some((
+                DeclValue::Scalar(path.join(".")),
+                SyntaxKind::Path,
+                first.span.merge(last.span),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The path of a `use` statement ending in `terminator`. Trivia, trailing
+/// comments included, is ignored.
+pub(crate) fn lower_import(node: &SyntaxNode, terminator: SyntaxKind) -> Option<Import> {
+    let parts: Vec<_> = node
+        .tokens()
+        .into_iter()
+        .filter(|t| {
+            let kind = t.kind();
+            !kind.is_trivia() && kind != SyntaxKind::Use && kind != terminator
+        })
+        .collect();
+
+    match parts.as_slice() {
+        [lit] if lit.kind() == SyntaxKind::StringLiteral => Some(Import {
+            path: lit.text().to_string(),
+        }),
+        [first, rest @ ..] if first.kind() == SyntaxKind::Ident => {
+            let mut path = first.text().to_string();
+            for pair in rest.chunks(2) {
+                let [sep, name] = pair else { return None };
+                if sep.kind() != SyntaxKind::DoubleColon || name.kind() != SyntaxKind::Ident {
+                    return None;
+                }
+                path.push_str("::");
+                path.push_str(name.text());
+            }
+            Some(Import { path })
+        }
+        _ => None,
+    }
+}
+
+/// Checks declared types against their values (an `int` value is accepted
+/// for a `float`), duplicate names, and empty strings. A value naming
+/// another variable, or a member of one, is not type checked.
+pub fn analyze(decls: &[VarDecl]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut seen = HashSet::new();
     for decl in decls {
-        if decl.ty != "string" {
-            println!("Error: Unsupported type '{}'", decl.ty);
+        if !seen.insert(decl.name.as_str()) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                message: format!("duplicate declaration of '{}'", decl.name),
+                span: decl.span,
+            });
         }
-        if decl.value.is_empty() {
-            println!("Warning: Empty string for '{}'", decl.name);
+        let reference = matches!(decl.value_kind, SyntaxKind::Ident | SyntaxKind::Path);
+        if let Some(ty) = decl.ty.as_ref().filter(|_| !reference) {
+            let found = literal_type(decl.value_kind).unwrap_or("unknown");
+            let message = if !matches!(ty.as_str(), "string" | "int" | "float" | "list") {
+                Some(format!("Unsupported type '{}'", ty))
+            } else if ty != found && !(ty == "float" && found == "int") {
+                Some(format!("type mismatch: expected {}, found {}", ty, found))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    message,
+                    span: decl.span,
+                });
+            }
+        }
+        if decl.value_kind == SyntaxKind::StringLiteral && decl.value == "" {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: format!("Empty string for '{}'", decl.name),
+                span: decl.span,
+            });
         }
     }
+    diagnostics
+}
+
+/// The type name a literal kind has, or `None` for kinds that are not
+/// literal values.
+fn literal_type(kind: SyntaxKind) -> Option<&'static str> {
+    match kind {
+        SyntaxKind::StringLiteral => Some("string"),
+        SyntaxKind::IntLiteral => Some("int"),
+        SyntaxKind::FloatLiteral => Some("float"),
+        SyntaxKind::BoolLiteral => Some("bool"),
+        SyntaxKind::ArrayValue => Some("list"),
+        _ => None,
+    }
+}
+
+/// Token kinds that can stand as a declaration's value on their own.
+fn is_value(kind: SyntaxKind) -> bool {
+    kind == SyntaxKind::Ident || (kind != SyntaxKind::ArrayValue && literal_type(kind).is_some())
+}
+
+/// The name→value pairs every compilation target emits, in source order.
+fn bindings(decls: &[VarDecl]) -> impl Iterator<Item = (&str, &DeclValue)> {
+    decls.iter().map(|d| (d.name.as_str(), &d.value))
 }
 
 pub fn compile(decls: &[VarDecl]) -> String {
-    let mut out = String::from("{\n");
-    for d in decls {
-        out.push_str(&format!("  \"{}\": \"{}\",\n", d.name, d.value));
+    let entries: Vec<_> = bindings(decls)
+        .map(|(name, value)| format!("  {}: {}", json_string(name), json_value(value)))
+        .collect();
+    if entries.is_empty() {
+        return String::from("{\n}");
+    }
+    format!("{{\n{}\n}}", entries.join(",\n"))
+}
+
+/// Like [`compile`], but serializes a name→value map with `serde_json`.
+#[cfg(feature = "serde")]
+pub fn compile_pretty(decls: &[VarDecl]) -> String {
+    let map: serde_json::Map<_, _> = bindings(decls)
+        .map(|(name, value)| {
+            let value = match value {
+                DeclValue::Scalar(text) => serde_json::Value::from(text.as_str()),
+                DeclValue::List(items) => serde_json::Value::from(items.as_slice()),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    serde_json::to_string_pretty(&map).expect("a string map always serializes")
+}
+
+/// Emits one `name: "value"` line per binding. Values are always
+/// double-quoted, and lists are flow sequences; names are quoted when YAML
+/// would read them as something other than a plain string.
+pub fn compile_yaml(decls: &[VarDecl]) -> String {
+    let mut out = String::new();
+    for (name, value) in bindings(decls) {
+        let key = if is_plain_yaml_key(name) {
+            name.to_string()
+        } else {
+            json_string(name)
+        };
+        out.push_str(&format!("{}: {}\n", key, json_value(value)));
+    }
+    if out.is_empty() {
+        out.push_str("{}\n");
+    }
+    out
+}
+
+/// Emits one `name = "value"` line per binding, quoting names that are not
+/// valid TOML bare keys. TOML rejects a repeated key, so only the last
+/// binding of a name is kept, as in a JSON object.
+pub fn compile_toml(decls: &[VarDecl]) -> String {
+    let mut out = String::new();
+    for (i, (name, value)) in bindings(decls).enumerate() {
+        if decls[i + 1..].iter().any(|later| later.name == name) {
+            continue;
+        }
+        let bare = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let key = if bare {
+            name.to_string()
+        } else {
+            json_string(name)
+        };
+        out.push_str(&format!("{} = {}\n", key, json_value(value)));
     }
-    out.push('}');
     out
 }
 
+fn is_plain_yaml_key(name: &str) -> bool {
+    const RESERVED: [&str; 9] = ["true", "false", "null", "yes", "no", "on", "off", "y", "n"];
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// A scalar as a JSON string, or a list as a JSON array of strings. Both
+/// are also valid YAML and TOML values.
+fn json_value(value: &DeclValue) -> String {
+    match value {
+        DeclValue::Scalar(text) => json_string(text),
+        DeclValue::List(items) => {
+            let items: Vec<_> = items.iter().map(|item| json_string(item)).collect();
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+/// Quotes `text` as a JSON string, escaping `"`, `\` and control characters.
+/// The result is also a valid YAML double-quoted scalar and TOML basic string.
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, cst_to_source, table_lex};
+
+    fn parse(source: &str) -> Vec<VarDecl> {
+        lower_to_ast(&parse_tokens_to_cst(&table_lex(source)))
+    }
+
+    #[test]
+    fn parses_each_kind_of_value() {
+        let decls = parse("let a = \"s\", b = 1, c = 1.5, d = true, e = false, f = a;");
+        let values: Vec<_> = decls
+            .iter()
+            .map(|d| (d.name.as_str(), d.value.as_scalar().unwrap(), d.value_kind))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("a", "s", SyntaxKind::StringLiteral),
+                ("b", "1", SyntaxKind::IntLiteral),
+                ("c", "1.5", SyntaxKind::FloatLiteral),
+                ("d", "true", SyntaxKind::BoolLiteral),
+                ("e", "false", SyntaxKind::BoolLiteral),
+                ("f", "a", SyntaxKind::Ident),
+            ]
+        );
+    }
+
+    fn list(items: &[&str]) -> DeclValue {
+        DeclValue::List(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn parses_array_values() {
+        let decls = parse("let a: list = [], b = [\"x\"], c = [\"x\", 2 ,\n  true,];");
+        let values: Vec<_> = decls.iter().map(|d| (d.name.as_str(), &d.value)).collect();
+        assert_eq!(
+            values,
+            [
+                ("a", &list(&[])),
+                ("b", &list(&["x"])),
+                ("c", &list(&["x", "2", "true"])),
+            ]
+        );
+        assert!(decls.iter().all(|d| d.value_kind == SyntaxKind::ArrayValue));
+        assert!(analyze(&decls).is_empty());
+        assert_eq!(compile(&decls[2..]), "{\n  \"c\": [\"x\", \"2\", \"true\"]\n}");
+    }
+
+    #[test]
+    fn arrays_are_array_value_nodes() {
+        let source = "let xs: list = [\"a\", \"b\"]; // two";
+        let (cst, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        assert!(errors.is_empty());
+        let decl = cst.child_nodes()[0];
+        let arrays = decl.child_nodes();
+        assert_eq!(arrays.len(), 1);
+        assert_eq!(arrays[0].kind(), SyntaxKind::ArrayValue);
+        assert_eq!(cst_to_source(&cst), source);
+        let text = |d: &VarDecl| &source[d.span.start..d.span.end];
+        assert_eq!(text(&parse(source)[0]), "xs: list = [\"a\", \"b\"]");
+    }
+
+    #[test]
+    fn unclosed_array_is_not_lowered() {
+        let source = "let xs = [\"a\" \"b\"];\nlet y = 1;";
+        let decls = parse(source);
+        assert_eq!(decls.len(), 1);
+        assert_eq!(decls[0].name, "y");
+        assert!(parse("let xs = [\"a\",;").is_empty());
+    }
+
+    #[test]
+    fn parser_records_what_it_expected() {
+        let source = "let a;\nlet b = 1;\nlet = 2;\nlet c";
+        let tokens = table_lex(source);
+        let config = ParseConfig::default();
+        let (root, errors) = Parser::new(&tokens, &config).parse();
+        assert_eq!(root.kind(), SyntaxKind::Root);
+        assert_eq!(root.child_nodes().len(), 4);
+        assert_eq!(cst_to_source(&root), source);
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected '=', found ';'",
+                "expected IDENT, found '='",
+                "expected '=', found end of input",
+            ]
+        );
+        assert_eq!(
+            errors[0],
+            ParseError::Expected {
+                expected: "'='".to_string(),
+                found: Some(SyntaxKind::Semicolon),
+                span: Span::new(5, 6),
+            }
+        );
+        let end = source.len();
+        assert!(matches!(
+            errors[2],
+            ParseError::Expected { found: None, span, .. } if span == Span::new(end, end)
+        ));
+        assert_eq!(parse(source).len(), 1);
+    }
+
+    #[test]
+    fn missing_value_is_an_error() {
+        for (source, found) in [("let x = ;", "';'"), ("let x =", "end of input")] {
+            let tokens = table_lex(source);
+            let config = ParseConfig::default();
+            let (root, errors) = Parser::new(&tokens, &config).parse();
+            assert_eq!(root.child_nodes()[0].kind(), SyntaxKind::VarDecl);
+            let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            assert_eq!(messages, [format!("expected a value, found {found}")]);
+        }
+        let tokens = table_lex("let x = ;");
+        let (_, errors) = Parser::new(&tokens, &ParseConfig::default()).parse();
+        assert_eq!(
+            errors,
+            [ParseError::Expected {
+                expected: "a value".to_string(),
+                found: Some(SyntaxKind::Semicolon),
+                span: Span::new(8, 9),
+            }]
+        );
+    }
+
+    #[test]
+    fn dotted_values_are_path_nodes() {
+        let source = "let x = a;\nlet y = a . b.c;";
+        let (cst, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        assert!(errors.is_empty());
+        let kinds: Vec<_> = cst
+            .child_nodes()
+            .iter()
+            .map(|d| d.child_nodes()[0].kind())
+            .collect();
+        assert_eq!(kinds, [SyntaxKind::Value, SyntaxKind::Path]);
+        assert_eq!(cst_to_source(&cst), source);
+
+        let decls = parse(source);
+        assert_eq!(decls[0].value, "a");
+        assert_eq!(decls[0].value_kind, SyntaxKind::Ident);
+        assert_eq!(decls[1].value, "a.b.c");
+        assert_eq!(decls[1].value_kind, SyntaxKind::Path);
+        assert!(analyze(&parse("let y: int = a.b;")).is_empty());
+    }
+
+    #[test]
+    fn path_ending_in_a_dot_is_an_error() {
+        let source = "let x = a.;\nlet y = 1;";
+        let (cst, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        assert_eq!(errors, [ParseError::MissingPathSegment { span: Span::new(9, 10) }]);
+        assert_eq!(errors[0].to_string(), "expected a name after '.' at 9");
+        assert_eq!(cst_to_source(&cst), source);
+        let decls = parse(source);
+        assert_eq!(decls.len(), 1);
+        assert_eq!(decls[0].name, "y");
+    }
+
+    #[test]
+    fn values_are_wrapped_in_value_nodes() {
+        let source = "let x = /* c */ y;";
+        let cst = parse_tokens_to_cst(&table_lex(source));
+        let decl = cst.child_nodes()[0];
+        let values = decl.child_nodes();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].kind(), SyntaxKind::Value);
+        // Leading trivia stays with the declaration.
+        assert_eq!(values[0].tokens().len(), 1);
+        assert_eq!(values[0].tokens()[0].text(), "y");
+        assert_eq!(cst_to_source(&cst), source);
+    }
+
+    #[test]
+    fn references_skip_the_type_check() {
+        assert!(analyze(&parse("let a: int = 1; let b: string = a;")).is_empty());
+        let diagnostics = analyze(&parse("let a: int = true;"));
+        assert_eq!(diagnostics[0].message, "type mismatch: expected int, found bool");
+    }
+
+    #[test]
+    fn parses_a_typed_declaration() {
+        let decls = parse("let x: string = \"hello\";");
+        assert_eq!(decls.len(), 1);
+        assert_eq!(decls[0].name, "x");
+        assert_eq!(decls[0].ty.as_deref(), Some("string"));
+        assert_eq!(decls[0].value, "hello");
+    }
+
+    #[test]
+    fn parses_two_grouped_bindings() {
+        let decls = parse("let a = \"x\", b = \"y\";");
+        let pairs: Vec<_> = decls
+            .iter()
+            .map(|d| (d.name.as_str(), d.value.as_scalar().unwrap()))
+            .collect();
+        assert_eq!(pairs, [("a", "x"), ("b", "y")]);
+        assert!(decls.iter().all(|d| d.ty.is_none()));
+    }
+
+    #[test]
+    fn grouped_bindings_get_their_own_spans() {
+        let source = "let a = \"x\", b = 2;";
+        let decls = parse(source);
+        let spans: Vec<_> = decls
+            .iter()
+            .map(|d| &source[d.span.start..d.span.end])
+            .collect();
+        assert_eq!(spans, ["a = \"x\"", "b = 2"]);
+        assert!(decls.iter().all(|d| d.mutable));
+    }
+
+    #[test]
+    fn parses_three_grouped_bindings_with_optional_types() {
+        let source =
+            "let a: string = \"x\",\n    b = \"y\",\n    c: string = \"z\";\nlet d = \"w\";";
+        let root = parse_tokens_to_cst(&table_lex(source));
+        assert_eq!(root.child_nodes().len(), 2);
+
+        let decls = lower_to_ast(&root);
+        let names: Vec<_> = decls.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+        let types: Vec<_> = decls.iter().map(|d| d.ty.as_deref()).collect();
+        assert_eq!(types, [Some("string"), None, Some("string"), None]);
+    }
+
+    #[test]
+    fn trailing_eof_ends_the_root() {
+        let source = "let a = \"x\"";
+        let lexer = crate::Lexer {
+            eof: true,
+            ..crate::Lexer::default()
+        };
+        let (root, errors) = parse_tokens_with_errors(&lexer.lex(source), &ParseConfig::default());
+        assert_eq!(root.tokens().last().unwrap().kind, SyntaxKind::Eof);
+        assert_eq!(root.child_nodes().len(), 1);
+        assert_eq!(cst_to_source(&root), source);
+        // The missing `;` is still reported at the end of the input.
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "missing ';' at 11");
+    }
+
+    #[test]
+    fn newline_can_terminate_statements() {
+        let config = ParseConfig {
+            terminator: SyntaxKind::NewLine,
+        };
+        let source = "let a = \"x\"\nlet b: string = \"y\"\n";
+        let root = parse_tokens_with_config(&table_lex(source), &config);
+
+        let nodes = root.child_nodes();
+        assert_eq!(nodes.len(), 2);
+        for node in &nodes {
+            assert_eq!(node.tokens().last().unwrap().kind, SyntaxKind::NewLine);
+        }
+        let names: Vec<_> = lower_to_ast(&root).into_iter().map(|d| d.name).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn empty_and_blank_input_round_trip() {
+        let newline = ParseConfig {
+            terminator: SyntaxKind::NewLine,
+        };
+        for config in [ParseConfig::default(), newline] {
+            for source in ["", "   \n  ", "\n\n", "// note\n"] {
+                let (root, errors) = parse_tokens_with_errors(&table_lex(source), &config);
+                assert_eq!(root.kind(), SyntaxKind::Root);
+                assert!(root.child_nodes().is_empty(), "{source:?}");
+                assert!(errors.is_empty(), "{source:?}: {errors:?}");
+                assert_eq!(cst_to_source(&root), source);
+            }
+        }
+    }
+
+    fn import_paths(source: &str) -> Vec<String> {
+        lower_statements(&parse_tokens_to_cst(&table_lex(source)))
+            .into_iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Import(import) => Some(import.path),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_string_path_import() {
+        assert_eq!(import_paths("use \"lib/common.dsl\";"), ["lib/common.dsl"]);
+    }
+
+    #[test]
+    fn parses_a_module_path_import() {
+        let source = "use std::fmt::Display;\nlet x = \"y\";";
+        assert_eq!(import_paths(source), ["std::fmt::Display"]);
+        let root = parse_tokens_to_cst(&table_lex(source));
+        assert_eq!(root.child_nodes()[0].kind(), SyntaxKind::ImportStmt);
+        assert_eq!(lower_to_ast(&root).len(), 1);
+    }
+
+    #[test]
+    fn imports_ignore_trailing_comments() {
+        let source = "use \"x\"; // note\nuse a::b; /* c */\n";
+        assert_eq!(import_paths(source), ["x", "a::b"]);
+        assert_eq!(crate::format_source(source), source);
+
+        let config = ParseConfig {
+            terminator: SyntaxKind::NewLine,
+        };
+        let root = parse_tokens_with_config(&table_lex("use a::b // note\n"), &config);
+        let stmts = lower_statements_with_config(&root, &config);
+        assert!(matches!(&stmts[..], [Stmt::Import(import)] if import.path == "a::b"));
+    }
+
+    #[test]
+    fn malformed_import_recovers_at_the_next_statement() {
+        for source in [
+            "use 42;\nlet x = \"y\";",
+            "use a:: let x = \"y\";",
+            "use ;let x = \"y\";",
+        ] {
+            let root = parse_tokens_to_cst(&table_lex(source));
+            let kinds: Vec<_> = root.child_nodes().iter().map(|n| n.kind()).collect();
+            assert_eq!(
+                kinds,
+                [SyntaxKind::ImportStmt, SyntaxKind::VarDecl],
+                "{source}"
+            );
+            assert!(import_paths(source).is_empty(), "{source}");
+            assert_eq!(lower_to_ast(&root)[0].name, "x", "{source}");
+        }
+    }
+
+    #[test]
+    fn compile_emits_valid_json() {
+        let json = compile(&parse("let a = \"x\";"));
+        assert_eq!(json, "{\n  \"a\": \"x\"\n}");
+
+        let json = compile(&parse("let a = \"x\";\nlet b = \"C:\\dir\";"));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["a"], "x");
+        assert_eq!(value["b"], "C:\\dir");
+    }
+
+    #[test]
+    fn compile_escapes_quotes_and_control_characters() {
+        let decls = [VarDecl {
+            name: "x".to_string(),
+            mutable: true,
+            ty: Some("string".to_string()),
+            value: DeclValue::Scalar("a\"b\nc".to_string()),
+            value_kind: SyntaxKind::StringLiteral,
+            span: Span::default(),
+            name_span: Span::default(),
+            type_span: None,
+            value_span: Span::default(),
+        }];
+        let json = compile(&decls);
+        assert_eq!(json, "{\n  \"x\": \"a\\\"b\\u000ac\"\n}");
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["x"], "a\"b\nc");
+    }
+
+    #[test]
+    fn analyze_reports_errors_and_warnings() {
+        let decl = |ty: &str, value: &str| VarDecl {
+            name: "a".to_string(),
+            mutable: true,
+            ty: Some(ty.to_string()),
+            value: DeclValue::Scalar(value.to_string()),
+            value_kind: SyntaxKind::StringLiteral,
+            span: Span::new(4, 20),
+            name_span: Span::new(4, 5),
+            type_span: Some(Span::new(7, 13)),
+            value_span: Span::new(17, 19),
+        };
+
+        let diagnostics = analyze(&[decl("number", "1")]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "Unsupported type 'number'");
+        assert_eq!(diagnostics[0].span, Span::new(4, 20));
+
+        let diagnostics = analyze(&[decl("string", "")]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].message, "Empty string for 'a'");
+    }
+
+    #[test]
+    fn lowered_declarations_span_name_to_value() {
+        let source = "let a: string = \"1\", b = \"\";";
+        let decls = parse(source);
+        let text = |d: &VarDecl| &source[d.span.start..d.span.end];
+        assert_eq!(text(&decls[0]), "a: string = \"1\"");
+        assert_eq!(text(&decls[1]), "b = \"\"");
+    }
+
+    #[test]
+    fn lowered_declarations_span_each_field() {
+        let source = "let a: string = \"hi\", b = \"\"\"x\"\"\", c = [1], d = e.f;";
+        let decls = parse(source);
+        let slice = |span: Span| &source[span.start..span.end];
+        assert_eq!(slice(decls[0].name_span), "a");
+        assert_eq!(decls[0].type_span.map(slice), Some("string"));
+        assert_eq!(slice(decls[0].value_span), "hi");
+        assert_eq!(decls[1].type_span, None);
+        assert_eq!(slice(decls[1].value_span), "x");
+        assert_eq!(slice(decls[2].value_span), "[1]");
+        assert_eq!(slice(decls[3].value_span), "e.f");
+
+        // The same with escapes decoded.
+        let escaped = "let a = \"a\\nb\";";
+        let lexer = Lexer {
+            unescape_strings: true,
+            ..Lexer::default()
+        };
+        let decls = lower_to_ast(&parse_tokens_to_cst(&lexer.lex(escaped)));
+        assert_eq!(decls[0].value, "a\nb");
+        let span = decls[0].value_span;
+        assert_eq!(&escaped[span.start..span.end], "a\\nb");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn var_decls_round_trip_through_serde_json() {
+        let decls = parse("let a: string = \"x\", b = \"y\";");
+        let json = serde_json::to_string(&decls).unwrap();
+        let back: Vec<VarDecl> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(
+            (back[0].name.as_str(), back[0].ty.as_deref()),
+            ("a", Some("string"))
+        );
+        assert_eq!((&back[1].value, back[1].span), (&decls[1].value, decls[1].span));
+
+        let pretty: serde_json::Value = serde_json::from_str(&compile_pretty(&decls)).unwrap();
+        let plain: serde_json::Value = serde_json::from_str(&compile(&decls)).unwrap();
+        assert_eq!(pretty, plain);
+    }
+
+    #[test]
+    fn compile_yaml_quotes_values_and_reserved_names() {
+        let decls = parse("let a = \"x: y\", null = \" #z\";\nlet b = \"\";");
+        let yaml = compile_yaml(&decls);
+        assert_eq!(yaml, "a: \"x: y\"\n\"null\": \" #z\"\nb: \"\"\n");
+
+        let map: std::collections::HashMap<String, String> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(map["a"], "x: y");
+        assert_eq!(map["null"], " #z");
+        assert_eq!(map["b"], "");
+        assert_eq!(compile_yaml(&[]), "{}\n");
+    }
+
+    #[test]
+    fn compile_toml_round_trips_through_the_toml_crate() {
+        let mut decls = parse("let a = \"x = \\\\y\";\nlet b = \"\";");
+        decls.push(VarDecl {
+            name: "two words".to_string(),
+            mutable: true,
+            ty: None,
+            value: DeclValue::Scalar("tab\there \"q\"".to_string()),
+            value_kind: SyntaxKind::StringLiteral,
+            span: Span::default(),
+            name_span: Span::default(),
+            type_span: None,
+            value_span: Span::default(),
+        });
+        let toml = compile_toml(&decls);
+        assert!(toml.starts_with("a = \"x = \\\\\\\\y\"\nb = \"\"\n\"two words\" = "));
+
+        let map: std::collections::HashMap<String, String> = toml::from_str(&toml).unwrap();
+        assert_eq!(map.len(), 3);
+        for decl in &decls {
+            assert_eq!(decl.value, map[&decl.name].as_str());
+        }
+
+        let toml = compile_toml(&parse("let x = \"a\"; let y = \"c\"; let x = \"b\";"));
+        assert_eq!(toml, "y = \"c\"\nx = \"b\"\n");
+        let map: std::collections::HashMap<String, String> = toml::from_str(&toml).unwrap();
+        assert_eq!(map["x"], "b");
+    }
+
+    #[test]
+    fn same_line_comment_trails_its_declaration() {
+        let source = "// header\nlet x: string = \"a\"; // note\n// leads y\nlet y = \"b\";\n";
+        let root = parse_tokens_to_cst(&table_lex(source));
+        let nodes = root.child_nodes();
+        assert_eq!(nodes.len(), 2);
+
+        // Re-emitting each statement keeps the note on the declaration's line.
+        let text = |node: &SyntaxNode| {
+            let tokens = node.tokens();
+            source[tokens[0].span.start..tokens.last().unwrap().span.end].to_string()
+        };
+        assert_eq!(text(nodes[0]), "let x: string = \"a\"; // note\n");
+        assert_eq!(text(nodes[1]), "let y = \"b\";\n");
+        assert_eq!(lower_to_ast(&root).len(), 2);
+    }
+
+    #[test]
+    fn analyze_flags_only_the_second_duplicate() {
+        let source = "let x = \"a\";\nlet y = \"b\";\nlet x = \"c\";";
+        let diagnostics = analyze(&parse(source));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "duplicate declaration of 'x'");
+        assert_eq!(
+            &source[diagnostics[0].span.start..diagnostics[0].span.end],
+            "x = \"c\""
+        );
+    }
+
+    #[test]
+    fn stray_content_is_reported_and_skipped() {
+        let source = "let x: string = \"a\"; garbage\nlet y = \"b\";";
+        let (root, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+
+        let decls = lower_to_ast(&root);
+        assert_eq!(decls.len(), 2);
+        assert_eq!(decls[1].name, "y");
+
+        let start = source.find("garbage").unwrap();
+        assert_eq!(
+            errors,
+            [ParseError::UnexpectedToken {
+                kind: SyntaxKind::Ident,
+                span: Span::new(start, start + "garbage".len()),
+            }]
+        );
+        let kinds: Vec<_> = root.child_nodes().iter().map(|n| n.kind()).collect();
+        assert_eq!(
+            kinds,
+            [SyntaxKind::VarDecl, SyntaxKind::Error, SyntaxKind::VarDecl]
+        );
+    }
+
+    #[test]
+    fn analyze_accepts_values_matching_their_declared_type() {
+        let source = "let a: string = \"x\", b: int = 1, c: float = 1.5, d: float = 2;";
+        let decls = parse(source);
+        assert_eq!(decls.len(), 4);
+        assert_eq!(decls[1].value_kind, SyntaxKind::IntLiteral);
+        assert!(analyze(&decls).is_empty());
+    }
+
+    #[test]
+    fn analyze_reports_type_mismatches() {
+        let diagnostics = analyze(&parse("let x: int = \"hi\";\nlet y: string = 4.2;"));
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "type mismatch: expected int, found string",
+                "type mismatch: expected string, found float",
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    }
+
+    #[test]
+    fn parses_let_and_const_declarations() {
+        let source = "let a = \"x\";\nconst B: int = 1;";
+        let root = parse_tokens_to_cst(&table_lex(source));
+        let kinds: Vec<_> = root.child_nodes().iter().map(|n| n.kind()).collect();
+        assert_eq!(kinds, [SyntaxKind::VarDecl, SyntaxKind::VarDecl]);
+
+        let decls = lower_to_ast(&root);
+        assert_eq!((decls[0].name.as_str(), decls[0].mutable), ("a", true));
+        assert_eq!((decls[1].name.as_str(), decls[1].mutable), ("B", false));
+    }
+
+    #[test]
+    fn each_statement_keyword_has_its_own_node() {
+        let source = "use a;\nlet b = \"1\";\nconst c = 2;\n= 3;";
+        let (root, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        let kinds: Vec<_> = root.child_nodes().iter().map(|n| n.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                SyntaxKind::ImportStmt,
+                SyntaxKind::VarDecl,
+                SyntaxKind::VarDecl,
+                SyntaxKind::Error,
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(cst_to_source(&root), source);
+    }
+
+    #[test]
+    fn missing_semicolon_is_reported_and_the_node_kept() {
+        let source = "let x = \"a\"";
+        let (root, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        assert_eq!(
+            errors,
+            [ParseError::MissingTerminator {
+                expected: SyntaxKind::Semicolon,
+                span: Span::new(11, 11),
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "missing ';' at 11");
+        assert_eq!(parse(source)[0].value, "a");
+        assert_eq!(root.child_nodes()[0].kind(), SyntaxKind::VarDecl);
+
+        let (_, errors) = parse_tokens_with_errors(
+            &table_lex("let x = 1\nlet y = 2;"),
+            &ParseConfig::default(),
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "missing ';' at 9");
+    }
+
+    #[test]
+    fn irregular_spacing_parses_the_same() {
+        let decls = parse("let   x:string=\"hi\" ;");
+        assert_eq!(decls.len(), 1);
+        assert_eq!(decls[0].name, "x");
+        assert_eq!(decls[0].ty.as_deref(), Some("string"));
+        assert_eq!(decls[0].value, "hi");
+
+        let decls = parse("let\n\tx\n:\n string\n=\n\"hi\"\n;");
+        assert_eq!((decls[0].name.as_str(), decls[0].value.as_scalar()), ("x", Some("hi")));
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn parse_error_labels_the_stray_token() {
+        use miette::Diagnostic;
+
+        let source = "let x = @;";
+        let (_, errors) = parse_tokens_with_errors(&table_lex(source), &ParseConfig::default());
+        let label = errors[0].labels().unwrap().next().unwrap();
+        assert_eq!(label.offset(), source.find('@').unwrap());
+    }
+}
@@ -0,0 +1,16 @@
+//! The types and functions most programs need, in one import.
+//!
+//! ```
+//! use table_driven_lexer::prelude::*;
+//!
+//! let tokens: Vec<Token> = table_lex("let x = 1;");
+//! assert_eq!(tokens[0].kind, SyntaxKind::Let);
+//! assert_eq!(lex("let x = 1;"), tokens);
+//!
+//! let root: SyntaxNode = parse_tokens_to_cst(&tokens);
+//! assert_eq!(lower_to_ast(&root)[0].name, "x");
+//! ```
+
+pub use crate::{
+    SyntaxKind, SyntaxNode, Token, TokenData, lex, lower_to_ast, parse_tokens_to_cst, table_lex,
+};
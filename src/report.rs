@@ -0,0 +1,51 @@
+use ariadne::{Config, Label, Report, ReportKind, Source};
+
+use crate::LexError;
+
+/// Renders one `ariadne` report per error, each pointing at the error's
+/// span. `color` toggles ANSI colours; turn it off for logs and tests.
+pub fn report_errors(source: &str, errors: &[LexError], color: bool) -> String {
+    let mut out = Vec::new();
+    for error in errors {
+        let span = error.span();
+        Report::build(ReportKind::Error, (), span.start)
+            .with_config(Config::default().with_color(color))
+            .with_message(error.to_string())
+            .with_label(Label::new(span.start..span.end).with_message(label(error)))
+            .finish()
+            .write(Source::from(source), &mut out)
+            .expect("writing to a Vec cannot fail");
+    }
+    String::from_utf8(out).expect("reports are UTF-8")
+}
+
+fn label(error: &LexError) -> &'static str {
+    match error {
+        LexError::UnexpectedChar { .. } => "not valid here",
+        LexError::UnterminatedString { .. } => "string starts here",
+        LexError::UnterminatedComment { .. } => "comment starts here",
+        LexError::InvalidNumber { .. } | LexError::InvalidDigit { .. } => "not a valid number",
+        LexError::InvalidEscape { .. } => "invalid escape",
+        LexError::TokenLimit { .. } => "lexing stopped here",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lex_errors, table_lex};
+
+    #[test]
+    fn reports_an_unterminated_string() {
+        let source = "let s = \"abc";
+        let errors = lex_errors(&table_lex(source));
+        let report = report_errors(source, &errors, false);
+
+        assert!(report.contains("unterminated string literal"), "{report}");
+        assert!(report.contains("let s = \"abc"), "{report}");
+        assert!(report.contains("string starts here"), "{report}");
+        assert!(report.contains(":1:9"), "{report}");
+        // No colour escapes in plain mode.
+        assert!(!report.contains('\x1b'), "{report}");
+    }
+}
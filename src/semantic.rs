@@ -1,69 +1,210 @@
 #![allow(unused)]
 
-use tower_lsp::lsp_types::{SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensResult};
+use std::collections::HashSet;
+use std::ops::Range;
 
-use crate::{lex, SyntaxKind};
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensResult,
+};
 
+use crate::{
+    lex, parse_tokens_to_cst, table_lex, LineIndex, Span, SyntaxKind, SyntaxNode, Token, TokenExt,
+};
 
+
+/// Full-document semantic tokens. Columns and lengths are measured in UTF-16
+/// code units, as LSP positions require.
 pub fn semantic_tokens_full(
     text: &str,
 ) -> Result<Option<SemanticTokensResult>, tower_lsp::jsonrpc::Error> {
-    let tokens = lex(text); // Token { kind, text }
-    let mut semantic_tokens = vec![];
+    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: semantic_tokens_in(text, 0..text.len()),
+    })))
+}
 
-    let mut char_offset = 0;
-    let mut prev_line = 0;
-    let mut prev_start_char = 0;
+/// Semantic tokens for the tokens overlapping the byte range `start..end`.
+/// The first emitted token is positioned absolutely, as in a full response.
+pub fn semantic_tokens_range(
+    text: &str,
+    start: usize,
+    end: usize,
+) -> Result<Option<SemanticTokensResult>, tower_lsp::jsonrpc::Error> {
+    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: semantic_tokens_in(text, start..end),
+    })))
+}
 
-    for token in tokens {
-        let token_start = char_offset;
-        let token_len = token.text.chars().count();
-        char_offset += token_len;
-
-        // Map byte offset to line and character position
-        let prefix = &text[..token_start];
-        let token_line = prefix.lines().count() - 1;
-        let token_col = prefix.lines().last().map_or(0, |l| l.len());
-
-        // Skip unknown tokens
-        let kind = match token.kind {
-            SyntaxKind::Let => SemanticTokenType::KEYWORD,
-            SyntaxKind::Ident => SemanticTokenType::VARIABLE,
-            SyntaxKind::Type => SemanticTokenType::TYPE,
-            SyntaxKind::StringLiteral => SemanticTokenType::STRING,
-            _ => continue,
-        };
+/// Semantic tokens on the zero-based `lines` only, so an editor can refresh
+/// just the lines it edited. Deltas are relative to the start of
+/// `lines.start`; segments of multi-line tokens outside the range are
+/// dropped.
+pub fn semantic_tokens_for_lines(text: &str, lines: Range<usize>) -> Vec<SemanticToken> {
+    let mut out = Vec::new();
+    let (mut line, mut col) = (0, 0);
+    let (mut prev_line, mut prev_col) = (lines.start, 0);
 
-        let delta_line = token_line - prev_line;
-        let delta_start = if delta_line == 0 {
-            token_col - prev_start_char
+    for token in semantic_tokens_in(text, 0..text.len()) {
+        line += token.delta_line as usize;
+        col = if token.delta_line == 0 {
+            col + token.delta_start as usize
         } else {
-            token_col
+            token.delta_start as usize
         };
+        if !lines.contains(&line) {
+            continue;
+        }
 
-        semantic_tokens.push(SemanticToken {
+        let delta_line = line - prev_line;
+        out.push(SemanticToken {
             delta_line: delta_line as u32,
-            delta_start: delta_start as u32,
-            length: token_len as u32,
-            token_type: token_type_index(kind),
-            token_modifiers_bitset: 0,
+            delta_start: if delta_line == 0 { col - prev_col } else { col } as u32,
+            ..token
         });
+        (prev_line, prev_col) = (line, col);
+    }
+
+    out
+}
+
+fn semantic_tokens_in(text: &str, range: Range<usize>) -> Vec<SemanticToken> {
+    let tokens = table_lex(text);
+    let declarations = declaration_spans(&tokens);
+    let index = LineIndex::new(text);
+    let mut semantic_tokens = vec![];
+
+    let mut prev_line = 0;
+    let mut prev_start_char = 0;
+
+    for token in tokens {
+        let token_source = &text[token.span.start..token.span.end];
+        let in_range = token.span.end > range.start && token.span.start < range.end;
+        let kind = semantic_token_type(token.kind()).filter(|_| in_range);
+
+        // Tokens spanning lines (block comments) are emitted per line.
+        let mut start = token.span.start;
+        for segment in token_source.split('\n') {
+            let (token_line, byte_col) = index.line_col(start);
+            let token_col = text[start - byte_col..start].encode_utf16().count();
+            start += segment.len() + 1;
+
+            let Some(kind) = kind.clone().filter(|_| !segment.is_empty()) else {
+                continue;
+            };
+
+            let delta_line = token_line - prev_line;
+            let delta_start = if delta_line == 0 {
+                token_col - prev_start_char
+            } else {
+                token_col
+            };
 
-        prev_line = token_line;
-        prev_start_char = token_col;
+            semantic_tokens.push(SemanticToken {
+                delta_line: delta_line as u32,
+                delta_start: delta_start as u32,
+                length: segment.encode_utf16().count() as u32,
+                token_type: token_type_index(kind),
+                token_modifiers_bitset: modifiers(&token, &declarations),
+            });
 
-        // Advance by 1 for separating tokens
-        char_offset += 1;
+            prev_line = token_line;
+            prev_start_char = token_col;
+        }
     }
 
-    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-        result_id: None,
-        data: semantic_tokens,
-    })))
+    semantic_tokens
+}
+
+/// The semantic type for a token kind, or `None` for kinds that are not
+/// highlighted.
+fn semantic_token_type(kind: SyntaxKind) -> Option<SemanticTokenType> {
+    match kind {
+        SyntaxKind::Let | SyntaxKind::Const | SyntaxKind::Use | SyntaxKind::BoolLiteral => {
+            Some(SemanticTokenType::KEYWORD)
+        }
+        SyntaxKind::Ident => Some(SemanticTokenType::VARIABLE),
+        SyntaxKind::Type => Some(SemanticTokenType::TYPE),
+        SyntaxKind::StringLiteral => Some(SemanticTokenType::STRING),
+        SyntaxKind::LineComment | SyntaxKind::DocComment | SyntaxKind::BlockComment => {
+            Some(SemanticTokenType::COMMENT)
+        }
+        SyntaxKind::IntLiteral | SyntaxKind::FloatLiteral => Some(SemanticTokenType::NUMBER),
+        _ => None,
+    }
 }
 
 const TOKEN_TYPE_INDEX: &[(&str, u32)] =
-    &[("keyword", 0), ("variable", 1), ("type", 2), ("string", 3)];
+    &[
+        ("keyword", 0),
+        ("variable", 1),
+        ("type", 2),
+        ("string", 3),
+        ("comment", 4),
+        ("number", 5),
+    ];
+
+/// The token types in index order. An LSP server must register exactly
+/// this list as the `SemanticTokensLegend` during `initialize`.
+pub fn semantic_token_legend() -> Vec<SemanticTokenType> {
+    let mut types = TOKEN_TYPE_INDEX.to_vec();
+    types.sort_by_key(|(_, idx)| *idx);
+    types
+        .into_iter()
+        .map(|(name, _)| SemanticTokenType::new(name))
+        .collect()
+}
+
+const TOKEN_MODIFIER_BIT: &[(&str, u32)] = &[("declaration", 0)];
+
+/// The token modifiers in bit order, registered alongside
+/// [`semantic_token_legend`].
+pub fn semantic_token_modifier_legend() -> Vec<SemanticTokenModifier> {
+    let mut modifiers = TOKEN_MODIFIER_BIT.to_vec();
+    modifiers.sort_by_key(|(_, bit)| *bit);
+    modifiers
+        .into_iter()
+        .map(|(name, _)| SemanticTokenModifier::new(name))
+        .collect()
+}
+
+fn token_modifier_bit(modifier: SemanticTokenModifier) -> u32 {
+    TOKEN_MODIFIER_BIT
+        .iter()
+        .find(|(name, _)| *name == modifier.as_str())
+        .map(|(_, bit)| 1 << bit)
+        .unwrap_or(0)
+}
+
+/// Spans of identifiers that name a binding in a `VarDecl`.
+fn declaration_spans(tokens: &[Token]) -> HashSet<Span> {
+    fn walk(node: &SyntaxNode, out: &mut HashSet<Span>) {
+        for child in node.child_nodes() {
+            if child.kind() == SyntaxKind::VarDecl {
+                out.extend(
+                    child
+                        .tokens()
+                        .into_iter()
+                        .filter(|t| t.kind() == SyntaxKind::Ident)
+                        .map(|t| t.span),
+                );
+            }
+            walk(child, out);
+        }
+    }
+
+    let mut spans = HashSet::new();
+    walk(&parse_tokens_to_cst(tokens), &mut spans);
+    spans
+}
+
+fn modifiers(token: &Token, declarations: &HashSet<Span>) -> u32 {
+    if declarations.contains(&token.span) {
+        token_modifier_bit(SemanticTokenModifier::DECLARATION)
+    } else {
+        0
+    }
+}
 
 fn token_type_index(typ: SemanticTokenType) -> u32 {
     TOKEN_TYPE_INDEX
@@ -73,49 +214,16 @@ fn token_type_index(typ: SemanticTokenType) -> u32 {
         .unwrap_or(0)
 }
 
+/// Semantic tokens for the whole of `source`, with columns and lengths in
+/// UTF-16 code units as the protocol expects, like [`semantic_tokens_full`].
 pub fn provide_semantic_tokens(source: &str) -> Vec<SemanticToken> {
-    let lexed = lex(source);
-    let mut char_offset = 0;
-    let mut current_line = 0;
-    let mut prev_start_char = 0;
-    let mut offset_start = 0;
-    let mut semantic_tokens = vec![];
-
-    for token in lexed {
-        let len = token.text.chars().count();
-        char_offset += len;
-        if token.kind == SyntaxKind::NewLine {
-            current_line += 1;
-        }
-        // Skip unknown tokens
-        let kind = match token.kind {
-            SyntaxKind::Let => SemanticTokenType::KEYWORD,
-            SyntaxKind::Ident => SemanticTokenType::VARIABLE,
-            SyntaxKind::Type => SemanticTokenType::TYPE,
-            SyntaxKind::StringLiteral => SemanticTokenType::STRING,
-            _ => {
-                offset_start += token.text.chars().count();
-                continue;
-            }
-        };
-
-        semantic_tokens.push(SemanticToken {
-            delta_line: current_line as u32,
-            delta_start: offset_start as u32,
-            length: len as u32,
-            token_type: token_type_index(kind),
-            token_modifiers_bitset: 0,
-        });
-
-        offset_start += token.text.chars().count();
-    }
-    semantic_tokens
+    semantic_tokens_in(source, 0..source.len())
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::{Token, TokenData};
+    use crate::{Span, Token, TokenData};
 
     use super::*;
 
@@ -128,47 +236,80 @@ mod tests {
             vec![
                 Token::new(TokenData {
                     kind: SyntaxKind::Let,
-                    text: "let".to_string()
+                    text: "let".into(),
+                    span: Span::new(0, 3),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Whitespace,
-                    text: " ".to_string()
+                    text: " ".into(),
+                    span: Span::new(3, 4),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Ident,
-                    text: "name".to_string()
+                    text: "name".into(),
+                    span: Span::new(4, 8),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Colon,
-                    text: ":".to_string()
+                    text: ":".into(),
+                    span: Span::new(8, 9),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Whitespace,
-                    text: " ".to_string()
+                    text: " ".into(),
+                    span: Span::new(9, 10),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Type,
-                    text: "string".to_string()
+                    text: "string".into(),
+                    span: Span::new(10, 16),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Whitespace,
-                    text: " ".to_string()
+                    text: " ".into(),
+                    span: Span::new(16, 17),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Equal,
-                    text: "=".to_string()
+                    text: "=".into(),
+                    span: Span::new(17, 18),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Whitespace,
-                    text: " ".to_string()
+                    text: " ".into(),
+                    span: Span::new(18, 19),
+                    symbol: None,
+                    raw: None,
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::StringLiteral,
-                    text: "Abhi".to_string()
+                    text: "Abhi".into(),
+                    span: Span::new(19, 25),
+                    symbol: None,
+                    raw: Some("\"Abhi\"".to_string()),
                 }),
                 Token::new(TokenData {
                     kind: SyntaxKind::Semicolon,
-                    text: ";".to_string()
+                    text: ";".into(),
+                    span: Span::new(25, 26),
+                    symbol: None,
+                    raw: None,
                 }),
             ]
         );
@@ -179,19 +320,19 @@ mod tests {
         let mut semantic_tokens = vec![];
 
         for token in lexed {
-            let len = token.text.chars().count();
+            let len = token.text().chars().count();
             char_offset += len;
-            if token.kind == SyntaxKind::NewLine {
+            if token.kind() == SyntaxKind::NewLine {
                 current_line += 1;
             }
             // Skip unknown tokens
-            let kind = match token.kind {
+            let kind = match token.kind() {
                 SyntaxKind::Let => SemanticTokenType::KEYWORD,
                 SyntaxKind::Ident => SemanticTokenType::VARIABLE,
                 SyntaxKind::Type => SemanticTokenType::TYPE,
                 SyntaxKind::StringLiteral => SemanticTokenType::STRING,
                 _ => {
-                    offset_start += token.text.chars().count();
+                    offset_start += token.text().chars().count();
                     continue;
                 }
             };
@@ -204,7 +345,7 @@ mod tests {
                 token_modifiers_bitset: 0,
             });
 
-            offset_start += token.text.chars().count();
+            offset_start += token.text().chars().count();
         }
 
         let input_sem = semantic_tokens;
@@ -237,4 +378,139 @@ mod tests {
             assert_eq!(len, 6);
         }
     }
+
+    #[test]
+    fn provide_semantic_tokens_delta_encodes_lines() {
+        let input = "let a: string = \"x\";\n  let b: string = \"y\";";
+        let tokens = provide_semantic_tokens(input);
+        assert_eq!(tokens.len(), 8);
+
+        // `a` is four columns after `let` on the same line.
+        assert_eq!((tokens[1].delta_line, tokens[1].delta_start), (0, 4));
+        // The string literal covers its quotes.
+        assert_eq!(tokens[3].length, 3);
+        // The second `let` starts a new line at absolute column 2.
+        assert_eq!((tokens[4].delta_line, tokens[4].delta_start), (1, 2));
+        assert_eq!((tokens[5].delta_line, tokens[5].delta_start), (0, 4));
+    }
+
+    fn full_data(text: &str) -> Vec<SemanticToken> {
+        match semantic_tokens_full(text) {
+            Ok(Some(SemanticTokensResult::Tokens(tokens))) => tokens.data,
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn semantic_tokens_full_handles_multibyte_identifiers() {
+        let data = full_data("let é = \"x\";");
+        assert_eq!(data.len(), 3);
+        assert_eq!((data[1].delta_start, data[1].length), (4, 1));
+        assert_eq!((data[2].delta_start, data[2].length), (4, 3));
+    }
+
+    #[test]
+    fn semantic_tokens_full_measures_utf16_code_units() {
+        // U+1F600 needs a surrogate pair in UTF-16.
+        let data = full_data("let s = \"😀\";\nlet t = \"a\";");
+        assert_eq!(data[2].length, 4);
+        assert_eq!((data[3].delta_line, data[3].delta_start), (1, 0));
+    }
+
+    #[test]
+    fn provided_tokens_measure_utf16_code_units_too() {
+        let text = "let s = \"😀\"; let t = 1;";
+        let provided = provide_semantic_tokens(text);
+        assert_eq!(provided, full_data(text));
+        assert_eq!(provided[2].length, 4);
+        // `let` after the string starts 4 UTF-16 units after its start.
+        assert_eq!(provided[3].delta_start, 6);
+    }
+
+    #[test]
+    fn legend_matches_token_type_indices() {
+        let legend = semantic_token_legend();
+        assert_eq!(legend.len(), TOKEN_TYPE_INDEX.len());
+        assert_eq!(token_type_index(SemanticTokenType::KEYWORD), 0);
+        for (idx, typ) in legend.into_iter().enumerate() {
+            assert_eq!(token_type_index(typ), idx as u32);
+        }
+    }
+
+    #[test]
+    fn declaration_names_carry_the_declaration_modifier() {
+        let declaration = token_modifier_bit(SemanticTokenModifier::DECLARATION);
+        assert_ne!(declaration, 0);
+        assert_eq!(semantic_token_modifier_legend(), [SemanticTokenModifier::DECLARATION]);
+
+        let data = full_data("let name: string = \"x\";");
+        let variable = token_type_index(SemanticTokenType::VARIABLE);
+        let names: Vec<_> = data.iter().filter(|t| t.token_type == variable).collect();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].token_modifiers_bitset, declaration);
+
+        // Only the name is a declaration.
+        assert!(data
+            .iter()
+            .filter(|t| t.token_type != variable)
+            .all(|t| t.token_modifiers_bitset == 0));
+
+        let provided = provide_semantic_tokens("let name: string = \"x\";");
+        assert_eq!(provided[1].token_modifiers_bitset, declaration);
+    }
+
+    #[test]
+    fn semantic_tokens_range_skips_tokens_before_the_range() {
+        let text = "let a: string = \"x\";\nlet b: string = \"y\";";
+        let second_line = text.find('\n').unwrap() + 1;
+        let data = match semantic_tokens_range(text, second_line, text.len()) {
+            Ok(Some(SemanticTokensResult::Tokens(tokens))) => tokens.data,
+            other => panic!("unexpected result: {other:?}"),
+        };
+
+        assert_eq!(data.len(), 4);
+        assert_eq!((data[0].delta_line, data[0].delta_start), (1, 0));
+        assert_eq!(data[0].token_type, token_type_index(SemanticTokenType::KEYWORD));
+        assert_eq!((data[1].delta_line, data[1].delta_start), (0, 4));
+        assert_eq!(&data[1..], &full_data(text)[5..]);
+    }
+
+    #[test]
+    fn semantic_tokens_for_lines_is_relative_to_the_first_line() {
+        let text = "let a = \"x\";\n  let b = \"y\"; /* c\nd */\nlet e = \"z\";";
+        let data = semantic_tokens_for_lines(text, 1..2);
+
+        let types: Vec<_> = data.iter().map(|t| t.token_type).collect();
+        let comment = token_type_index(SemanticTokenType::COMMENT);
+        let keyword = token_type_index(SemanticTokenType::KEYWORD);
+        let variable = token_type_index(SemanticTokenType::VARIABLE);
+        let string = token_type_index(SemanticTokenType::STRING);
+        // The block comment's second line is outside the range.
+        assert_eq!(types, [keyword, variable, string, comment]);
+        assert_eq!((data[0].delta_line, data[0].delta_start), (0, 2));
+        assert_eq!((data[1].delta_line, data[1].delta_start), (0, 4));
+        assert_eq!(&data[1..], &full_data(text)[4..7]);
+    }
+
+    #[test]
+    fn comments_and_numbers_are_highlighted() {
+        let data = full_data("// the answer\nlet x = 42; /* a\nb */");
+        let types: Vec<_> = data.iter().map(|t| t.token_type).collect();
+        let comment = token_type_index(SemanticTokenType::COMMENT);
+        let number = token_type_index(SemanticTokenType::NUMBER);
+        let keyword = token_type_index(SemanticTokenType::KEYWORD);
+        let variable = token_type_index(SemanticTokenType::VARIABLE);
+        assert_eq!(types, [comment, keyword, variable, number, comment, comment]);
+
+        // The block comment is split at the line break.
+        assert_eq!((data[4].delta_line, data[4].length), (0, 4));
+        assert_eq!((data[5].delta_line, data[5].delta_start, data[5].length), (1, 0, 4));
+
+        let provided: Vec<_> = provide_semantic_tokens("let y = 4.2; // note")
+            .iter()
+            .map(|t| t.token_type)
+            .collect();
+        assert!(provided.contains(&number));
+        assert!(provided.contains(&comment));
+    }
 }
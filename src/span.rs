@@ -0,0 +1,187 @@
+/// Byte range `start..end` into the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether `offset` lies in `start..end`; the end is exclusive.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// Maps byte offsets to zero-based line and column positions and back.
+/// Columns count bytes from the start of the line, except that a tab
+/// counts as `tab_width` columns (one by default). Line starts are found
+/// once, up front, so each lookup is a binary search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Byte offset of the start of every line; the first is always 0.
+    line_starts: Vec<usize>,
+    /// Byte offset of every tab.
+    tabs: Vec<usize>,
+    tab_width: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        Self::with_tab_width(source, 1)
+    }
+
+    /// Like [`LineIndex::new`], but each tab advances the column by
+    /// `tab_width`, wherever it sits on the line.
+    pub fn with_tab_width(source: &str, tab_width: usize) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let tabs = source.match_indices('\t').map(|(i, _)| i).collect();
+        LineIndex {
+            line_starts,
+            tabs,
+            tab_width,
+        }
+    }
+
+    /// The line and column of `offset`. An offset at a line break belongs
+    /// to the line the break ends.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let tabs = self.tabs_between(start, offset).len();
+        (line, offset - start - tabs + tabs * self.tab_width)
+    }
+
+    /// The byte offset of `col` on `line`. A column inside a tab's width
+    /// maps to the tab. Panics if there is no such line.
+    pub fn offset(&self, line: usize, col: usize) -> usize {
+        let mut pos = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(usize::MAX);
+        let mut remaining = col;
+        for &tab in self.tabs_between(pos, end) {
+            if tab - pos >= remaining {
+                break;
+            }
+            remaining -= tab - pos;
+            if remaining < self.tab_width {
+                return tab;
+            }
+            remaining -= self.tab_width;
+            pos = tab + 1;
+        }
+        pos + remaining
+    }
+
+    /// The tabs at byte offsets `start..end`.
+    fn tabs_between(&self, start: usize, end: usize) -> &[usize] {
+        let from = self.tabs.partition_point(|&tab| tab < start);
+        let to = self.tabs.partition_point(|&tab| tab < end);
+        &self.tabs[from..to]
+    }
+}
+
+#[cfg(feature = "miette")]
+impl From<Span> for miette::SourceSpan {
+    fn from(span: Span) -> Self {
+        (span.start, span.len()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains_excludes_the_end() {
+        let span = Span::new(2, 5);
+        assert!(!span.contains(1));
+        assert!(span.contains(2));
+        assert!(span.contains(4));
+        assert!(!span.contains(5));
+        assert!(!Span::new(3, 3).contains(3));
+    }
+
+    #[test]
+    fn merge_covers_both_spans() {
+        assert_eq!(Span::new(2, 5).merge(Span::new(7, 9)), Span::new(2, 9));
+        assert_eq!(Span::new(7, 9).merge(Span::new(2, 5)), Span::new(2, 9));
+        assert_eq!(Span::new(2, 9).merge(Span::new(4, 5)), Span::new(2, 9));
+        assert_eq!(Span::new(2, 5).merge(Span::new(5, 5)), Span::new(2, 5));
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        assert_eq!(Span::new(2, 5).len(), 3);
+        assert!(Span::new(4, 4).is_empty());
+        assert!(!Span::new(4, 5).is_empty());
+    }
+
+    #[test]
+    fn line_col_on_the_first_line() {
+        let index = LineIndex::new("let x;\nlet y;");
+        assert_eq!(index.line_col(0), (0, 0));
+        assert_eq!(index.line_col(4), (0, 4));
+        assert_eq!(index.offset(0, 4), 4);
+    }
+
+    #[test]
+    fn line_col_after_a_newline() {
+        let source = "let x;\nlet y;\n\nz";
+        let index = LineIndex::new(source);
+        // The line break itself ends the first line.
+        assert_eq!(index.line_col(6), (0, 6));
+        assert_eq!(index.line_col(7), (1, 0));
+        assert_eq!(index.line_col(11), (1, 4));
+        assert_eq!(index.line_col(15), (3, 0));
+        assert_eq!(index.offset(1, 4), 11);
+        assert_eq!(&source[index.offset(3, 0)..], "z");
+    }
+
+    #[test]
+    fn line_col_at_the_end_of_input() {
+        let source = "a\nbc";
+        let index = LineIndex::new(source);
+        assert_eq!(index.line_col(source.len()), (1, 2));
+        assert_eq!(index.offset(1, 2), source.len());
+
+        let index = LineIndex::new("a\n");
+        assert_eq!(index.line_col(2), (1, 0));
+        assert_eq!(LineIndex::new("").line_col(0), (0, 0));
+    }
+
+    #[test]
+    fn tabs_count_tab_width_columns() {
+        let source = "let a;\n\tlet b;\n\t\tc";
+        let index = LineIndex::with_tab_width(source, 4);
+        let b = source.find('b').unwrap();
+        assert_eq!(index.line_col(b - 4), (1, 4));
+        assert_eq!(index.line_col(b), (1, 8));
+        assert_eq!(index.offset(1, 8), b);
+        // Inside the tab's width.
+        assert_eq!(index.offset(1, 2), b - 5);
+        assert_eq!(index.line_col(source.len() - 1), (2, 8));
+        assert_eq!(index.offset(2, 8), source.len() - 1);
+
+        // The default width keeps byte columns.
+        assert_eq!(LineIndex::new(source).line_col(b), (1, 5));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{SyntaxKind, Token};
+
+/// A cursor over a token slice, for parsers that step through tokens one
+/// at a time instead of indexing.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenStream<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        TokenStream { tokens, pos: 0 }
+    }
+
+    /// The token at the cursor, trivia included.
+    pub fn peek(&self) -> Option<&'a Token> {
+        self.nth(0)
+    }
+
+    /// The token `n` places after the cursor, without moving it.
+    pub fn nth(&self, n: usize) -> Option<&'a Token> {
+        self.tokens.get(self.pos + n)
+    }
+
+    /// Consumes and returns the token at the cursor.
+    pub fn bump(&mut self) -> Option<&'a Token> {
+        let tok = self.peek()?;
+        self.pos += 1;
+        Some(tok)
+    }
+
+    /// Whether the token at the cursor has kind `kind`.
+    pub fn at(&self, kind: SyntaxKind) -> bool {
+        self.peek().is_some_and(|tok| tok.kind == kind)
+    }
+
+    /// Consumes the token at the cursor if it has kind `kind`, returning
+    /// whether it did.
+    pub fn eat(&mut self, kind: SyntaxKind) -> bool {
+        let matched = self.at(kind);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+
+    /// How many tokens have been consumed.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The tokens not yet consumed.
+    pub fn as_slice(&self) -> &'a [Token] {
+        &self.tokens[self.pos..]
+    }
+}
+
+impl<'a> From<&'a [Token]> for TokenStream<'a> {
+    fn from(tokens: &'a [Token]) -> Self {
+        TokenStream::new(tokens)
+    }
+}
+
+/// Iterates over the tokens not yet consumed.
+impl<'a> IntoIterator for TokenStream<'a> {
+    type Item = &'a Token;
+    type IntoIter = std::slice::Iter<'a, Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table_lex;
+
+    #[test]
+    fn peek_and_bump_step_through_the_tokens() {
+        let tokens = table_lex("let x");
+        let mut stream = TokenStream::new(&tokens);
+        assert_eq!(stream.peek().unwrap().kind, SyntaxKind::Let);
+        assert_eq!(stream.nth(2).unwrap().text, "x");
+        assert_eq!(stream.bump().unwrap().kind, SyntaxKind::Let);
+        assert_eq!(stream.position(), 1);
+        assert_eq!(stream.as_slice(), &tokens[1..]);
+        stream.bump();
+        stream.bump();
+        assert!(stream.is_at_end());
+        assert_eq!(stream.peek(), None);
+        assert_eq!(stream.bump(), None);
+        assert_eq!(stream.position(), 3);
+    }
+
+    #[test]
+    fn eat_consumes_only_a_match() {
+        let tokens = table_lex("x=");
+        let mut stream = TokenStream::from(tokens.as_slice());
+        assert!(stream.at(SyntaxKind::Ident));
+        assert!(!stream.eat(SyntaxKind::Equal));
+        assert_eq!(stream.position(), 0);
+        assert!(stream.eat(SyntaxKind::Ident));
+        assert!(stream.eat(SyntaxKind::Equal));
+        assert!(!stream.eat(SyntaxKind::Equal));
+        assert!(!stream.at(SyntaxKind::Equal));
+    }
+
+    #[test]
+    fn iterates_over_the_rest() {
+        let tokens = table_lex("a b");
+        let mut stream = TokenStream::new(&tokens);
+        stream.bump();
+        let rest: Vec<_> = stream.into_iter().map(|t| t.text.as_ref()).collect();
+        assert_eq!(rest, [" ", "b"]);
+    }
+}
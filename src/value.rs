@@ -0,0 +1,102 @@
+use thiserror::Error;
+
+use crate::radix_prefix;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NumberError {
+    #[error("invalid number literal `{0}`")]
+    Invalid(String),
+    #[error("integer literal `{0}` cannot be represented exactly")]
+    Inexact(String),
+}
+
+/// How number literals are decoded into `f64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberParsing {
+    /// Accept the nearest `f64`, silently rounding large integers.
+    #[default]
+    Lossy,
+    /// Reject integer literals that `f64` cannot represent exactly, such as
+    /// IDs above 2^53.
+    Strict,
+}
+
+impl NumberParsing {
+    pub fn parse(self, text: &str) -> Result<f64, NumberError> {
+        match self {
+            NumberParsing::Lossy => parse_number_value(text),
+            NumberParsing::Strict => parse_number_value_strict(text),
+        }
+    }
+}
+
+/// Decodes the text of an `IntLiteral` or `FloatLiteral`, ignoring `_`
+/// digit separators.
+pub fn parse_number_value(text: &str) -> Result<f64, NumberError> {
+    let value = match integer_value(text) {
+        Some(Ok(exact)) => Some(exact as f64),
+        // Too wide for `u128`; only a decimal one can still round to a float.
+        Some(Err(_)) if radix_prefix(text).is_some() => None,
+        _ => text.replace('_', "").parse::<f64>().ok(),
+    };
+    value.ok_or_else(|| NumberError::Invalid(text.to_string()))
+}
+
+/// Like [`parse_number_value`], but integer literals must round-trip through
+/// `f64` unchanged. Float literals are approximate by nature and pass through.
+pub fn parse_number_value_strict(text: &str) -> Result<f64, NumberError> {
+    let value = parse_number_value(text)?;
+    match integer_value(text) {
+        None => Ok(value),
+        Some(Ok(exact)) if value as u128 == exact => Ok(value),
+        Some(_) => Err(NumberError::Inexact(text.to_string())),
+    }
+}
+
+/// The exact value of an integer literal, in any base, or `None` for
+/// text that isn't one.
+fn integer_value(text: &str) -> Option<Result<u128, std::num::ParseIntError>> {
+    let (radix, digits) = match radix_prefix(text) {
+        Some(radix) => (radix, &text[2..]),
+        None => (10, text),
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    Some(u128::from_str_radix(&digits, radix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2^53 + 1, the first integer an f64 cannot hold.
+    const BEYOND_F64: &str = "9007199254740993";
+
+    #[test]
+    fn lossy_mode_rounds_large_integers() {
+        assert_eq!(parse_number_value(BEYOND_F64), Ok(9007199254740992.0));
+        assert_eq!(NumberParsing::Lossy.parse(BEYOND_F64), Ok(9007199254740992.0));
+    }
+
+    #[test]
+    fn strict_mode_rejects_inexact_integers() {
+        let err = NumberError::Inexact(BEYOND_F64.to_string());
+        assert_eq!(parse_number_value_strict(BEYOND_F64), Err(err.clone()));
+        assert_eq!(NumberParsing::Strict.parse(BEYOND_F64), Err(err));
+        assert_eq!(parse_number_value_strict("9007199254740992"), Ok(9007199254740992.0));
+        assert_eq!(parse_number_value_strict("0.1"), Ok(0.1));
+        assert_eq!(parse_number_value_strict("1_000"), Ok(1000.0));
+        assert_eq!(parse_number_value_strict("0x1F"), Ok(31.0));
+        assert_eq!(parse_number_value("0o17"), Ok(15.0));
+        assert_eq!(parse_number_value("0b1010"), Ok(10.0));
+        assert_eq!(parse_number_value(&"9".repeat(40)), Ok(1e40));
+    }
+
+    #[test]
+    fn invalid_text_is_an_error_in_both_modes() {
+        assert!(matches!(parse_number_value("abc"), Err(NumberError::Invalid(_))));
+        assert!(matches!(parse_number_value_strict("abc"), Err(NumberError::Invalid(_))));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const SOURCE: &str = "let x: string = \"hello\";\nlet y = \"world\";\n";
+
+fn temp_source(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.dsl", name, std::process::id()));
+    std::fs::write(&path, SOURCE).unwrap();
+    path
+}
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_table-driven-lexer"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn compiles_a_file_to_json_by_default() {
+    let path = temp_source("cli-json");
+    let out = run(&[path.to_str().unwrap()]);
+    let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(value["x"], "hello");
+    assert_eq!(value["y"], "world");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn prints_the_selected_stage() {
+    let path = temp_source("cli-stages");
+    let path = path.to_str().unwrap();
+
+    let tokens = run(&["--tokens", path]);
+    assert!(tokens.starts_with("LET: \"let\"\nWHITESPACE: \" \"\nIDENT: \"x\"\n"));
+
+    let cst = run(&["--cst", path]);
+    assert!(cst.starts_with("ROOT\n  VARDECL\n    LET: \"let\"\n"));
+    assert_eq!(cst.matches("VARDECL").count(), 2);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn reads_stdin_without_a_path() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_table-driven-lexer"))
+        .arg("--json")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(SOURCE.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value["x"], "hello");
+}
+
+#[test]
+fn rejects_unknown_options() {
+    let output = Command::new(env!("CARGO_BIN_EXE_table-driven-lexer"))
+        .arg("--bogus")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn compiles_to_each_format() {
+    let path = temp_source("cli-formats");
+    let path = path.to_str().unwrap();
+
+    let json: HashMap<String, String> =
+        serde_json::from_str(&run(&[path, "--format", "json"])).unwrap();
+    let yaml: HashMap<String, String> =
+        serde_yaml::from_str(&run(&[path, "--format", "yaml"])).unwrap();
+    let toml: HashMap<String, String> = toml::from_str(&run(&["--format", "toml", path])).unwrap();
+
+    assert_eq!(json["x"], "hello");
+    assert_eq!(json["y"], "world");
+    assert_eq!(yaml, json);
+    assert_eq!(toml, json);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rejects_unknown_formats() {
+    let output = Command::new(env!("CARGO_BIN_EXE_table-driven-lexer"))
+        .args(["--format", "xml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}